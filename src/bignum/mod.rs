@@ -0,0 +1,676 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Rem, Sub};
+use std::str::FromStr;
+
+/// Operands with at least this many limbs
+/// are multiplied with Karatsuba.
+const KARATSUBA_THRESHOLD: usize = 32;
+
+/// Arbitrary-precision unsigned integer.
+///
+/// Limbs are stored little-endian in base 2^32 with
+/// no trailing zero limbs, so zero is an empty vector.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint { limbs: vec![] }
+    }
+
+    pub fn one() -> Self {
+        BigUint::from(1u32)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Number of significant bits, zero for zero.
+    pub fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(&top) => self.limbs.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// The value as u64, if it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        Some(
+            self.limbs
+                .iter()
+                .rev()
+                .fold(0, |n, &limb| n << 32 | limb as u64),
+        )
+    }
+
+    fn from_limbs(limbs: Vec<u32>) -> Self {
+        BigUint { limbs }.normalize()
+    }
+
+    fn normalize(mut self) -> Self {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    /// Computes self^exp by repeated squaring.
+    pub fn pow(&self, mut exp: u64) -> Self {
+        let mut result = BigUint::one();
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// Quotient and remainder. Panics if divisor is zero.
+    pub fn div_rem(&self, divisor: &BigUint) -> (BigUint, BigUint) {
+        assert!(!divisor.is_zero(), "division by zero");
+
+        if self < divisor {
+            return (BigUint::zero(), self.clone());
+        }
+        if divisor.limbs.len() == 1 {
+            let (quotient, rem) = self.div_rem_small(divisor.limbs[0]);
+            return (quotient, BigUint::from(rem));
+        }
+
+        let (quotient, rem) = div_rem_limbs(&self.limbs, &divisor.limbs);
+        (BigUint::from_limbs(quotient), BigUint::from_limbs(rem))
+    }
+
+    /// Divide by a single limb, returning quotient and remainder.
+    fn div_rem_small(&self, divisor: u32) -> (BigUint, u32) {
+        assert!(divisor != 0, "division by zero");
+
+        let mut quotient = vec![0; self.limbs.len()];
+        let mut rem: u64 = 0;
+        for (i, &limb) in self.limbs.iter().enumerate().rev() {
+            let cur = (rem << 32) | limb as u64;
+            quotient[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+
+        (BigUint::from_limbs(quotient), rem as u32)
+    }
+
+    /// self * factor + addend, for single limbs.
+    fn mul_add_small(&self, factor: u32, addend: u32) -> BigUint {
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+        let mut carry = addend as u64;
+        for &limb in self.limbs.iter() {
+            let cur = limb as u64 * factor as u64 + carry;
+            limbs.push(cur as u32);
+            carry = cur >> 32;
+        }
+        limbs.push(carry as u32);
+        BigUint::from_limbs(limbs)
+    }
+
+    /// Parse digits in the given radix, from 2 to 36.
+    /// No sign, prefix or separators are accepted.
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<BigUint, ParseBigUintError> {
+        assert!((2..=36).contains(&radix), "radix must be in 2..=36");
+
+        if s.is_empty() {
+            return Err(ParseBigUintError::Empty);
+        }
+
+        // Feed as many digits at a time as fit in a limb.
+        let mut chunk_len = 1;
+        while (radix as u64).pow(chunk_len + 1) <= u32::MAX as u64 {
+            chunk_len += 1;
+        }
+
+        let digits: Vec<u32> = s
+            .chars()
+            .map(|c| c.to_digit(radix).ok_or(ParseBigUintError::InvalidDigit(c)))
+            .collect::<Result<_, _>>()?;
+
+        let mut result = BigUint::zero();
+        for chunk in digits.chunks(chunk_len as usize) {
+            let value = chunk.iter().fold(0, |acc, &d| acc * radix + d);
+            result = result.mul_add_small(radix.pow(chunk.len() as u32), value);
+        }
+
+        Ok(result)
+    }
+
+    /// Digits in the given radix, from 2 to 36, lowercase.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "radix must be in 2..=36");
+
+        if self.is_zero() {
+            return "0".to_string();
+        }
+
+        // Peel off as many digits at a time as fit in a limb.
+        let mut chunk_len = 1;
+        while (radix as u64).pow(chunk_len + 1) <= u32::MAX as u64 {
+            chunk_len += 1;
+        }
+        let chunk_radix = radix.pow(chunk_len);
+
+        let mut digits = vec![];
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, mut rem) = rest.div_rem_small(chunk_radix);
+            for _ in 0..chunk_len {
+                digits.push(std::char::from_digit(rem % radix, radix).unwrap());
+                rem /= radix;
+            }
+            rest = quotient;
+        }
+
+        while digits.len() > 1 && digits.last() == Some(&'0') {
+            digits.pop();
+        }
+        digits.iter().rev().collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseBigUintError {
+    Empty,
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ParseBigUintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseBigUintError::Empty => write!(f, "cannot parse integer from empty string"),
+            ParseBigUintError::InvalidDigit(c) => write!(f, "invalid digit {:?}", c),
+        }
+    }
+}
+
+impl Error for ParseBigUintError {}
+
+/// Decimal digits, or hex digits after a `0x` prefix.
+impl FromStr for BigUint {
+    type Err = ParseBigUintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => BigUint::from_str_radix(hex, 16),
+            None => BigUint::from_str_radix(s, 10),
+        }
+    }
+}
+
+fn add_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+
+    let mut limbs = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let sum = limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        limbs.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+
+    limbs
+}
+
+/// a - b, where a must not be smaller than b.
+fn sub_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut diff = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        limbs.push(diff as u32);
+    }
+    debug_assert_eq!(borrow, 0, "subtraction underflow");
+
+    limbs
+}
+
+/// Add x * 2^(32 * shift) into acc, which must be long enough.
+fn add_shifted(acc: &mut [u32], x: &[u32], shift: usize) {
+    let mut carry = 0u64;
+    let mut i = 0;
+    while i < x.len() || carry > 0 {
+        let sum = acc[shift + i] as u64 + *x.get(i).unwrap_or(&0) as u64 + carry;
+        acc[shift + i] = sum as u32;
+        carry = sum >> 32;
+        i += 1;
+    }
+}
+
+fn trim(limbs: &[u32]) -> &[u32] {
+    let len = limbs.iter().rposition(|&x| x != 0).map_or(0, |i| i + 1);
+    &limbs[..len]
+}
+
+fn mul_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.len().min(b.len()) < KARATSUBA_THRESHOLD {
+        schoolbook_mul(a, b)
+    } else {
+        karatsuba_mul(a, b)
+    }
+}
+
+fn schoolbook_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = limbs[i + j] as u64 + x as u64 * y as u64 + carry;
+            limbs[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        limbs[i + b.len()] = carry as u32;
+    }
+    limbs
+}
+
+/// Split both operands at half, a = a1 B + a0 and b = b1 B + b0, then
+/// a b = z2 B^2 + z1 B + z0 with z1 = (a0 + a1)(b0 + b1) - z2 - z0.
+fn karatsuba_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let half = a.len().max(b.len()) / 2;
+    let (a0, a1) = a.split_at(half.min(a.len()));
+    let (b0, b1) = b.split_at(half.min(b.len()));
+    let (a0, b0) = (trim(a0), trim(b0));
+
+    let z0 = mul_limbs(a0, b0);
+    let z2 = mul_limbs(a1, b1);
+    let z1 = mul_limbs(&add_limbs(a0, a1), &add_limbs(b0, b1));
+    let z1 = sub_limbs(&sub_limbs(&z1, &z0), &z2);
+
+    let mut limbs = vec![0u32; a.len() + b.len() + 1];
+    add_shifted(&mut limbs, trim(&z0), 0);
+    add_shifted(&mut limbs, trim(&z1), half);
+    add_shifted(&mut limbs, trim(&z2), 2 * half);
+    limbs.truncate(a.len() + b.len());
+    limbs
+}
+
+/// Knuth's algorithm D. The divisor has at least two
+/// limbs and the dividend is not smaller than it.
+fn div_rem_limbs(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    const BASE: u64 = 1 << 32;
+    let n = v.len();
+    let m = u.len() - n;
+
+    // Shift so the top limb of the divisor has its high bit set.
+    let shift = v[n - 1].leading_zeros();
+    let shl = |limbs: &[u32], i: usize| -> u32 {
+        let low = if i > 0 { limbs[i - 1] as u64 } else { 0 };
+        let high = *limbs.get(i).unwrap_or(&0) as u64;
+        (((high << 32 | low) << shift) >> 32) as u32
+    };
+    let vn: Vec<u32> = (0..n).map(|i| shl(v, i)).collect();
+    let mut un: Vec<u32> = (0..=u.len()).map(|i| shl(u, i)).collect();
+
+    let mut quotient = vec![0u32; m + 1];
+    for j in (0..=m).rev() {
+        let num = (un[j + n] as u64) << 32 | un[j + n - 1] as u64;
+        let mut qhat = num / vn[n - 1] as u64;
+        let mut rhat = num % vn[n - 1] as u64;
+        while qhat >= BASE || qhat * vn[n - 2] as u64 > (rhat << 32 | un[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += vn[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+
+        // Multiply and subtract qhat * vn from the window.
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let p = qhat * vn[i] as u64;
+            let t = un[i + j] as i64 - borrow - (p & 0xFFFF_FFFF) as i64;
+            un[i + j] = t as u32;
+            borrow = (p >> 32) as i64 - (t >> 32);
+        }
+        let t = un[j + n] as i64 - borrow;
+        un[j + n] = t as u32;
+
+        quotient[j] = qhat as u32;
+        if t < 0 {
+            // qhat was one too large, add the divisor back.
+            quotient[j] -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = un[i + j] as u64 + vn[i] as u64 + carry;
+                un[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            un[j + n] = un[j + n].wrapping_add(carry as u32);
+        }
+    }
+
+    // Undo the normalization shift.
+    let rem = (0..n)
+        .map(|i| {
+            let wide = (un[i + 1] as u64) << 32 | un[i] as u64;
+            (wide >> shift) as u32
+        })
+        .collect();
+
+    (quotient, rem)
+}
+
+impl From<u32> for BigUint {
+    fn from(n: u32) -> Self {
+        BigUint::from_limbs(vec![n])
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        BigUint::from(n as u128)
+    }
+}
+
+impl From<usize> for BigUint {
+    fn from(n: usize) -> Self {
+        BigUint::from(n as u128)
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(mut n: u128) -> Self {
+        let mut limbs = vec![];
+        while n > 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+        BigUint { limbs }
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        BigUint {
+            limbs: add_limbs(&self.limbs, &other.limbs),
+        }
+    }
+}
+
+impl Sub for &BigUint {
+    type Output = BigUint;
+
+    /// Panics if other is greater than self.
+    fn sub(self, other: &BigUint) -> BigUint {
+        assert!(self >= other, "subtraction underflow");
+        BigUint::from_limbs(sub_limbs(&self.limbs, &other.limbs))
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+        BigUint::from_limbs(mul_limbs(&self.limbs, &other.limbs))
+    }
+}
+
+impl Div for &BigUint {
+    type Output = BigUint;
+
+    /// Panics if other is zero.
+    fn div(self, other: &BigUint) -> BigUint {
+        self.div_rem(other).0
+    }
+}
+
+impl Rem for &BigUint {
+    type Output = BigUint;
+
+    /// Panics if other is zero.
+    fn rem(self, other: &BigUint) -> BigUint {
+        self.div_rem(other).1
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: BigUint) -> BigUint {
+        &self + &other
+    }
+}
+
+impl Sub for BigUint {
+    type Output = BigUint;
+
+    fn sub(self, other: BigUint) -> BigUint {
+        &self - &other
+    }
+}
+
+impl Mul for BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: BigUint) -> BigUint {
+        &self * &other
+    }
+}
+
+impl Div for BigUint {
+    type Output = BigUint;
+
+    fn div(self, other: BigUint) -> BigUint {
+        &self / &other
+    }
+}
+
+impl Rem for BigUint {
+    type Output = BigUint;
+
+    fn rem(self, other: BigUint) -> BigUint {
+        &self % &other
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "", &self.to_str_radix(10))
+    }
+}
+
+impl fmt::LowerHex for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_str_radix(16))
+    }
+}
+
+impl fmt::UpperHex for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_str_radix(16).to_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number built from arbitrary limbs.
+    fn big(limbs: Vec<u32>) -> BigUint {
+        BigUint::from_limbs(limbs)
+    }
+
+    #[quickcheck]
+    fn test_add_sub(a: u64, b: u64) -> bool {
+        let (x, y) = (BigUint::from(a), BigUint::from(b));
+        let sum = &x + &y;
+        sum == BigUint::from(a as u128 + b as u128) && &sum - &y == x
+    }
+
+    #[quickcheck]
+    fn test_mul(a: u64, b: u64) -> bool {
+        &BigUint::from(a) * &BigUint::from(b) == BigUint::from(a as u128 * b as u128)
+    }
+
+    #[quickcheck]
+    fn test_karatsuba(a: Vec<u32>, b: Vec<u32>, seed: u32) -> bool {
+        // Repeat limbs to get past the threshold.
+        let grow = |xs: Vec<u32>| -> Vec<u32> {
+            let mut xs = xs;
+            xs.push(seed | 1);
+            xs.iter().cycle().take(xs.len() * 17).copied().collect()
+        };
+        let (a, b) = (grow(a), grow(b));
+        trim(&karatsuba_mul(&a, &b)) == trim(&schoolbook_mul(&a, &b))
+    }
+
+    #[quickcheck]
+    fn test_div_rem(a: Vec<u32>, b: Vec<u32>) -> bool {
+        let (a, b) = (big(a), big(b));
+        if b.is_zero() {
+            return true;
+        }
+        let (q, r) = a.div_rem(&b);
+        r < b && &(&q * &b) + &r == a
+    }
+
+    #[quickcheck]
+    fn test_div_rem_exact(q: Vec<u32>, b: Vec<u32>, r: Vec<u32>) -> bool {
+        // Build a = q * b + r with r < b and check both parts come back.
+        let (q, b) = (big(q), big(b));
+        if b.is_zero() {
+            return true;
+        }
+        let r = &big(r) % &b;
+        let a = &(&q * &b) + &r;
+        a.div_rem(&b) == (q, r)
+    }
+
+    #[test]
+    fn test_div_rem_add_back() {
+        // Known to need the rare add back step of algorithm D.
+        let a = big(vec![0, 0, 0x8000_0000, 0x7fff_ffff]);
+        let b = big(vec![1, 0, 0x8000_0000]);
+        let (q, r) = a.div_rem(&b);
+        assert_eq!(&(&q * &b) + &r, a);
+        assert!(r < b);
+
+        assert_eq!(
+            &BigUint::from(u128::MAX) / &BigUint::from(u64::MAX),
+            BigUint::from(u64::MAX as u128 + 2)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_div_by_zero() {
+        let _ = &BigUint::one() / &BigUint::zero();
+    }
+
+    #[quickcheck]
+    fn test_pow(base: u8, exp: u8) -> bool {
+        let exp = exp % 16;
+        BigUint::from(base as u32).pow(exp as u64) == BigUint::from((base as u128).pow(exp as u32))
+    }
+
+    #[quickcheck]
+    fn test_to_u64(a: u128) -> bool {
+        let expected = if a > u64::MAX as u128 {
+            None
+        } else {
+            Some(a as u64)
+        };
+        BigUint::from(a).to_u64() == expected
+    }
+
+    #[quickcheck]
+    fn test_cmp(a: u128, b: u128) -> bool {
+        BigUint::from(a).cmp(&BigUint::from(b)) == a.cmp(&b)
+    }
+
+    #[quickcheck]
+    fn test_display(a: u128) -> bool {
+        BigUint::from(a).to_string() == a.to_string()
+            && format!("{:x}", BigUint::from(a)) == format!("{:x}", a)
+            && format!("{:#X}", BigUint::from(a)) == format!("{:#X}", a)
+    }
+
+    #[quickcheck]
+    fn test_parse_roundtrip(limbs: Vec<u32>, radix: u8) -> bool {
+        let radix = radix as u32 % 35 + 2;
+        let a = big(limbs);
+        BigUint::from_str_radix(&a.to_str_radix(radix), radix) == Ok(a)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("0".parse(), Ok(BigUint::zero()));
+        assert_eq!("000123".parse(), Ok(BigUint::from(123u32)));
+        assert_eq!("0xfF".parse(), Ok(BigUint::from(255u32)));
+        assert_eq!(
+            "340282366920938463463374607431768211455".parse(),
+            Ok(BigUint::from(u128::MAX))
+        );
+        assert_eq!("".parse::<BigUint>(), Err(ParseBigUintError::Empty));
+        assert_eq!("0x".parse::<BigUint>(), Err(ParseBigUintError::Empty));
+        assert_eq!(
+            "12a".parse::<BigUint>(),
+            Err(ParseBigUintError::InvalidDigit('a'))
+        );
+        assert_eq!(
+            "-1".parse::<BigUint>(),
+            Err(ParseBigUintError::InvalidDigit('-'))
+        );
+    }
+
+    #[test]
+    fn test_display_large() {
+        let ten_pow_19 = BigUint::from(10_000_000_000_000_000_000u64);
+        let ten_pow_38 = &ten_pow_19 * &ten_pow_19;
+        let ten_pow_76 = &ten_pow_38 * &ten_pow_38;
+        assert_eq!(ten_pow_76.to_string(), format!("1{}", "0".repeat(76)));
+        assert_eq!(BigUint::from(10u32).pow(76), ten_pow_76);
+        assert_eq!(format!("{:>3}", BigUint::zero()), "  0");
+        assert_eq!(BigUint::from(1u32 << 31).bits(), 32);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sub_underflow() {
+        let _ = &BigUint::from(1u32) - &BigUint::from(2u32);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::bignum::BigUint;
+
+/// Associative operation with an identity element.
+pub trait Monoid: Clone {
+    /// Identity of the same shape as self, e.g. a matrix
+    /// of the same size. Sizeless monoids ignore self.
+    fn identity_like(&self) -> Self;
+
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// Computes x^n under the monoid operation by
+/// binary exponentiation, in O(log n) combines.
+pub fn power<M: Monoid>(x: &M, mut n: u64) -> M {
+    let mut result = x.identity_like();
+    let mut base = x.clone();
+    while n > 0 {
+        if n & 1 == 1 {
+            result = result.combine(&base);
+        }
+        n >>= 1;
+        // Skip the last squaring, it is never used
+        // and may overflow fixed width numbers.
+        if n > 0 {
+            base = base.combine(&base);
+        }
+    }
+
+    result
+}
+
+/// Two monoids sharing elements, where times
+/// distributes over plus and zero annihilates.
+pub trait Semiring: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn plus(&self, other: &Self) -> Self;
+    fn times(&self, other: &Self) -> Self;
+}
+
+macro_rules! impl_semiring {
+    ($($t:ty)*) => {
+        $(
+            impl Semiring for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn plus(&self, other: &Self) -> Self {
+                    self + other
+                }
+
+                fn times(&self, other: &Self) -> Self {
+                    self * other
+                }
+            }
+        )*
+    };
+}
+
+impl_semiring!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+impl Semiring for BigUint {
+    fn zero() -> Self {
+        BigUint::zero()
+    }
+
+    fn one() -> Self {
+        BigUint::one()
+    }
+
+    fn plus(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn times(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+/// Semiring elements under multiplication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Product<T>(pub T);
+
+impl<T: Semiring> Monoid for Product<T> {
+    fn identity_like(&self) -> Self {
+        Product(T::one())
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        Product(self.0.times(&other.0))
+    }
+}
+
+/// Strings under concatenation. Powers repeat the string.
+impl Monoid for String {
+    fn identity_like(&self) -> Self {
+        String::new()
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        format!("{}{}", self, other)
+    }
+}
+
+/// Permutation of 0..n, mapping i to self.0[i].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permutation(Vec<usize>);
+
+impl Permutation {
+    /// Returns None unless map holds each of 0..map.len() once.
+    pub fn new(map: Vec<usize>) -> Option<Self> {
+        let mut seen = vec![false; map.len()];
+        for &i in map.iter() {
+            if i >= map.len() || seen[i] {
+                return None;
+            }
+            seen[i] = true;
+        }
+        Some(Permutation(map))
+    }
+
+    pub fn identity(n: usize) -> Self {
+        Permutation((0..n).collect())
+    }
+
+    pub fn apply(&self, i: usize) -> usize {
+        self.0[i]
+    }
+}
+
+/// Composition, where `p.combine(&q)` applies q first, then p.
+impl Monoid for Permutation {
+    fn identity_like(&self) -> Self {
+        Permutation::identity(self.0.len())
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        assert_eq!(self.0.len(), other.0.len(), "permutation sizes differ");
+        Permutation(other.0.iter().map(|&i| self.0[i]).collect())
+    }
+}
+
+/// Element of the (min, +) semiring. Plus takes the minimum,
+/// times adds, so zero is infinity and one is 0.
+///
+/// Powers of a matrix of edge weights give the
+/// shortest walks using exactly k edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tropical {
+    Finite(i64),
+    Infinity,
+}
+
+impl Semiring for Tropical {
+    fn zero() -> Self {
+        Tropical::Infinity
+    }
+
+    fn one() -> Self {
+        Tropical::Finite(0)
+    }
+
+    fn plus(&self, other: &Self) -> Self {
+        *self.min(other)
+    }
+
+    fn times(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Tropical::Finite(a), Tropical::Finite(b)) => Tropical::Finite(a.saturating_add(*b)),
+            _ => Tropical::Infinity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::matrix::Matrix;
+    use super::*;
+
+    #[quickcheck]
+    fn test_power_product(x: u8, n: u8) -> bool {
+        let n = n % 8;
+        power(&Product(x as u64), n as u64) == Product((x as u64).pow(n as u32))
+    }
+
+    #[quickcheck]
+    fn test_power_string(s: String, n: u8) -> bool {
+        power(&s, n as u64) == s.repeat(n as usize)
+    }
+
+    #[quickcheck]
+    fn test_power_permutation(seed: Vec<usize>, n: u8) -> bool {
+        // Build a permutation by sorting positions by seed.
+        let mut map: Vec<usize> = (0..seed.len()).collect();
+        map.sort_by_key(|&i| seed[i]);
+        let p = Permutation::new(map).unwrap();
+
+        let mut expected = p.identity_like();
+        for _ in 0..n {
+            expected = p.combine(&expected);
+        }
+        power(&p, n as u64) == expected
+    }
+
+    #[test]
+    fn test_permutation_new() {
+        assert!(Permutation::new(vec![2, 0, 1]).is_some());
+        assert!(Permutation::new(vec![0, 0, 1]).is_none());
+        assert!(Permutation::new(vec![0, 3, 1]).is_none());
+
+        // A 3-cycle returns to the identity after 3 steps.
+        let cycle = Permutation::new(vec![1, 2, 0]).unwrap();
+        assert_eq!(cycle.combine(&cycle).apply(0), 2);
+        assert_eq!(power(&cycle, 3), Permutation::identity(3));
+        assert_eq!(power(&cycle, 1_000_000_000_000), cycle);
+    }
+
+    #[test]
+    fn test_tropical_shortest_walks() {
+        use Tropical::{Finite, Infinity};
+
+        // 0 -> 1 (1), 1 -> 2 (2), 0 -> 2 (10), 2 -> 0 (1)
+        let weights = Matrix::from_rows(vec![
+            vec![Infinity, Finite(1), Finite(10)],
+            vec![Infinity, Infinity, Finite(2)],
+            vec![Finite(1), Infinity, Infinity],
+        ]);
+
+        // Exactly two edges from 0 to 2 must go through 1.
+        assert_eq!(weights.pow(2)[(0, 2)], Finite(3));
+        // No walk of exactly two edges from 0 to 1.
+        assert_eq!(weights.pow(2)[(0, 1)], Infinity);
+        // Around the cycle 0 -> 1 -> 2 -> 0 once.
+        assert_eq!(weights.pow(3)[(0, 0)], Finite(4));
+        assert_eq!(weights.pow(0)[(1, 1)], Finite(0));
+    }
+}
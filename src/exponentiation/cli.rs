@@ -0,0 +1,351 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::algebra::{power, Monoid, Product};
+use super::fibonacci::{fibonacci_big, fibonacci_matrix, fibonacci_mod, pisano_period};
+use super::modular::{add_mod, checked_pow, mul_mod, pow_mod};
+use crate::bignum::BigUint;
+
+/// How the fibonacci numbers are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FibAlgorithm {
+    /// Fast doubling, O(log n) multiplications.
+    Doubling,
+    /// Powers of [[0, 1], [1, 1]].
+    Matrix,
+    /// n additions.
+    Linear,
+}
+
+impl FromStr for FibAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubling" => Ok(FibAlgorithm::Doubling),
+            "matrix" => Ok(FibAlgorithm::Matrix),
+            "linear" => Ok(FibAlgorithm::Linear),
+            _ => Err(format!("unknown algorithm: {}", s)),
+        }
+    }
+}
+
+/// How base^exp is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowAlgorithm {
+    /// Repeated squaring on the numbers directly.
+    Binary,
+    /// Generic `power` over the multiplicative monoid.
+    Monoid,
+    /// exp multiplications.
+    Naive,
+}
+
+impl FromStr for PowAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" => Ok(PowAlgorithm::Binary),
+            "monoid" => Ok(PowAlgorithm::Monoid),
+            "naive" => Ok(PowAlgorithm::Naive),
+            _ => Err(format!("unknown algorithm: {}", s)),
+        }
+    }
+}
+
+/// Half-open range of indices written `a..b`, or `a..=b`
+/// to include the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexRange(pub Range<u128>);
+
+impl FromStr for IndexRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid range: {}", s);
+
+        let (start, end, inclusive) = match s.find("..=") {
+            Some(i) => (&s[..i], &s[i + 3..], true),
+            None => {
+                let i = s.find("..").ok_or_else(invalid)?;
+                (&s[..i], &s[i + 2..], false)
+            }
+        };
+        let start: u128 = start.parse().map_err(|_| invalid())?;
+        let mut end: u128 = end.parse().map_err(|_| invalid())?;
+        if inclusive {
+            end = end.checked_add(1).ok_or_else(invalid)?;
+        }
+
+        Ok(IndexRange(start..end))
+    }
+}
+
+/// F(n), or F(n) mod m when a modulus is given.
+///
+/// Without a modulus n must fit in usize. With one, every
+/// algorithm works on residues in u64, and linear first
+/// reduces n by the Pisano period.
+pub fn fib(n: u128, modulus: Option<u64>, algorithm: FibAlgorithm) -> Result<BigUint, String> {
+    let m = match modulus {
+        Some(0) => return Err("modulus must be positive".to_string()),
+        Some(m) => m,
+        None => {
+            let n = usize::try_from(n).map_err(|_| format!("index too large: {}", n))?;
+            return Ok(fib_exact(n, algorithm));
+        }
+    };
+
+    let result = match algorithm {
+        FibAlgorithm::Doubling => fibonacci_mod(n, m),
+        FibAlgorithm::Matrix => {
+            // [[0, 1], [1, 1]]^n, split as n = hi * 2^64 + lo.
+            let step = ModMatrix::new([[0, 1], [1, 1]], m);
+            let (hi, lo) = ((n >> 64) as u64, n as u64);
+            let mut result = power(&step, lo);
+            if hi > 0 {
+                let shift = power(&power(&step, 1 << 32), 1 << 32);
+                result = result.combine(&power(&shift, hi));
+            }
+            result.rows[0][1]
+        }
+        FibAlgorithm::Linear => {
+            let (mut a, mut b) = (0, 1 % m);
+            let mut i = n % pisano_period(m);
+            while i > 0 {
+                let next = add_mod(a, b, m);
+                a = b;
+                b = next;
+                i -= 1;
+            }
+            a
+        }
+    };
+    Ok(BigUint::from(result))
+}
+
+fn fib_exact(n: usize, algorithm: FibAlgorithm) -> BigUint {
+    match algorithm {
+        FibAlgorithm::Doubling => fibonacci_big(n),
+        FibAlgorithm::Matrix => fibonacci_matrix(n),
+        FibAlgorithm::Linear => {
+            let (mut a, mut b) = (BigUint::zero(), BigUint::one());
+            for _ in 0..n {
+                let next = &a + &b;
+                a = b;
+                b = next;
+            }
+            a
+        }
+    }
+}
+
+/// 2x2 matrices of residues modulo m under multiplication.
+#[derive(Debug, Clone, Copy)]
+struct ModMatrix {
+    rows: [[u64; 2]; 2],
+    modulus: u64,
+}
+
+impl ModMatrix {
+    fn new(rows: [[u64; 2]; 2], modulus: u64) -> Self {
+        ModMatrix {
+            rows: [
+                [rows[0][0] % modulus, rows[0][1] % modulus],
+                [rows[1][0] % modulus, rows[1][1] % modulus],
+            ],
+            modulus,
+        }
+    }
+}
+
+impl Monoid for ModMatrix {
+    fn identity_like(&self) -> Self {
+        ModMatrix::new([[1, 0], [0, 1]], self.modulus)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        let (a, b, m) = (&self.rows, &other.rows, self.modulus);
+        let entry = |i: usize, j: usize| {
+            add_mod(
+                mul_mod(a[i][0], b[0][j], m),
+                mul_mod(a[i][1], b[1][j], m),
+                m,
+            )
+        };
+        ModMatrix {
+            rows: [[entry(0, 0), entry(0, 1)], [entry(1, 0), entry(1, 1)]],
+            modulus: m,
+        }
+    }
+}
+
+/// Residues modulo m under multiplication.
+#[derive(Debug, Clone, Copy)]
+struct ModProduct {
+    value: u64,
+    modulus: u64,
+}
+
+impl Monoid for ModProduct {
+    fn identity_like(&self) -> Self {
+        ModProduct {
+            value: 1 % self.modulus,
+            modulus: self.modulus,
+        }
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        ModProduct {
+            value: mul_mod(self.value, other.value, self.modulus),
+            modulus: self.modulus,
+        }
+    }
+}
+
+/// base^exp, or base^exp mod m when a modulus is given.
+pub fn pow(
+    base: &BigUint,
+    exp: u64,
+    modulus: Option<u64>,
+    algorithm: PowAlgorithm,
+) -> Result<BigUint, String> {
+    let m = match modulus {
+        Some(0) => return Err("modulus must be positive".to_string()),
+        Some(m) => m,
+        None => {
+            return Ok(match algorithm {
+                // Powers that fit in u64 come from the tables.
+                PowAlgorithm::Binary => match base.to_u64().and_then(|b| checked_pow(b, exp)) {
+                    Some(p) => BigUint::from(p),
+                    None => base.pow(exp),
+                },
+                PowAlgorithm::Monoid => power(&Product(base.clone()), exp).0,
+                PowAlgorithm::Naive => (0..exp).fold(BigUint::one(), |acc, _| &acc * base),
+            });
+        }
+    };
+
+    // The remainder is below m, so it fits in u64.
+    let base = (base % &BigUint::from(m)).to_u64().unwrap();
+    let result = match algorithm {
+        PowAlgorithm::Binary => pow_mod(base, exp, m),
+        PowAlgorithm::Monoid => {
+            power(
+                &ModProduct {
+                    value: base,
+                    modulus: m,
+                },
+                exp,
+            )
+            .value
+        }
+        PowAlgorithm::Naive => (0..exp).fold(1 % m, |acc, _| mul_mod(acc, base, m)),
+    };
+    Ok(BigUint::from(result))
+}
+
+/// Run f, returning its result and how long it took.
+pub fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+/// One "n F(n)" line per index in range.
+pub fn fib_range(
+    range: &IndexRange,
+    modulus: Option<u64>,
+    algorithm: FibAlgorithm,
+) -> Result<String, String> {
+    let mut out = String::new();
+    for n in range.0.clone() {
+        writeln!(out, "{} {}", n, fib(n, modulus, algorithm)?).unwrap();
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIB_ALGORITHMS: [FibAlgorithm; 3] = [
+        FibAlgorithm::Doubling,
+        FibAlgorithm::Matrix,
+        FibAlgorithm::Linear,
+    ];
+
+    const POW_ALGORITHMS: [PowAlgorithm; 3] = [
+        PowAlgorithm::Binary,
+        PowAlgorithm::Monoid,
+        PowAlgorithm::Naive,
+    ];
+
+    #[quickcheck]
+    fn test_fib_algorithms_agree(n: u8, m: u16) -> bool {
+        let n = n as u128 * 3;
+        let modulus = if m == 0 { None } else { Some(m as u64) };
+        let expected = fib(n, modulus, FibAlgorithm::Doubling);
+        FIB_ALGORITHMS
+            .iter()
+            .all(|&algorithm| fib(n, modulus, algorithm) == expected)
+    }
+
+    #[quickcheck]
+    fn test_pow_algorithms_agree(base: u64, exp: u8, m: u64) -> bool {
+        let base = BigUint::from(base);
+        let modulus = if m == 0 { None } else { Some(m) };
+        let expected = pow(&base, exp as u64, modulus, PowAlgorithm::Binary);
+        POW_ALGORITHMS
+            .iter()
+            .all(|&algorithm| pow(&base, exp as u64, modulus, algorithm) == expected)
+    }
+
+    #[test]
+    fn test_fib() {
+        let big: BigUint = "354224848179261915075".parse().unwrap();
+        assert_eq!(fib(100, None, FibAlgorithm::Matrix), Ok(big));
+        // Far beyond usize, but fine modulo m.
+        assert_eq!(
+            fib(u128::MAX, Some(10), FibAlgorithm::Linear),
+            fib(u128::MAX, Some(10), FibAlgorithm::Doubling)
+        );
+        assert_eq!(
+            fib(u128::MAX, Some(1_000_000_007), FibAlgorithm::Matrix),
+            fib(u128::MAX, Some(1_000_000_007), FibAlgorithm::Doubling)
+        );
+        assert!(fib(u128::MAX, None, FibAlgorithm::Doubling).is_err());
+        assert!(fib(5, Some(0), FibAlgorithm::Doubling).is_err());
+    }
+
+    #[test]
+    fn test_pow() {
+        let big: BigUint = "1267650600228229401496703205376".parse().unwrap();
+        assert_eq!(
+            pow(&BigUint::from(2u32), 100, None, PowAlgorithm::Monoid),
+            Ok(big)
+        );
+        assert_eq!(
+            pow(&BigUint::from(3u32), 200, Some(1000), PowAlgorithm::Binary),
+            Ok(BigUint::from(pow_mod(3, 200, 1000)))
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!("3..7".parse(), Ok(IndexRange(3..7)));
+        assert_eq!("3..=7".parse(), Ok(IndexRange(3..8)));
+        assert!("3..".parse::<IndexRange>().is_err());
+        assert!("7".parse::<IndexRange>().is_err());
+
+        let range = "8..=10".parse().unwrap();
+        assert_eq!(
+            fib_range(&range, None, FibAlgorithm::Linear),
+            Ok("8 21\n9 34\n10 55\n".to_string())
+        );
+    }
+}
@@ -1,7 +1,196 @@
-/// Computes the nth fibonacci number by
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+
+use super::algebra::Semiring;
+use super::matrix::Matrix;
+use super::modular::{add_mod, factorize, lcm, mul_mod, sub_mod};
+use crate::bignum::BigUint;
+
+/// F(0), F(1), ..., F(93): every fibonacci number that fits in u64.
+pub const FIB_TABLE: [u64; 94] = fib_table();
+
+const fn fib_table() -> [u64; 94] {
+    let mut table = [0; 94];
+    table[1] = 1;
+    let mut i = 2;
+    while i < table.len() {
+        table[i] = table[i - 1] + table[i - 2];
+        i += 1;
+    }
+    table
+}
+
+/// F(n) from `FIB_TABLE`, if it fits in usize.
+const fn lookup(n: usize) -> Option<usize> {
+    if n < FIB_TABLE.len() && FIB_TABLE[n] <= usize::MAX as u64 {
+        Some(FIB_TABLE[n] as usize)
+    } else {
+        None
+    }
+}
+
+/// Computes the nth fibonacci number by looking it up in
+/// `FIB_TABLE`, which holds every one that fits in usize.
+/// Panics if F(n) overflows usize.
+pub const fn fibonacci(n: usize) -> usize {
+    match lookup(n) {
+        Some(f) => f,
+        None => panic!("fibonacci overflows usize"),
+    }
+}
+
+/// Generates a fast doubling fibonacci over `$t`
+/// that returns None once F(n) overflows.
+macro_rules! checked_fibonacci {
+    ($(#[$doc:meta])* $name:ident, $t:ty) => {
+        $(#[$doc])*
+        pub fn $name(n: usize) -> Option<$t> {
+            if let Some(&f) = FIB_TABLE.get(n) {
+                return <$t>::try_from(f).ok();
+            }
+
+            let mut pair: ($t, $t) = (0, 1);
+
+            // Every intermediate value is at most F(n),
+            // so an overflow means F(n) does not fit.
+            for bit in (1..usize::BITS - n.leading_zeros()).rev() {
+                let (a, b) = pair;
+                let c = a.checked_mul(b.checked_mul(2)? - a)?;
+                let d = a.checked_mul(a)?.checked_add(b.checked_mul(b)?)?;
+                pair = if (n >> bit) & 1 == 0 { (c, d) } else { (d, c.checked_add(d)?) };
+            }
+
+            let (a, b) = pair;
+            if n & 1 == 0 {
+                a.checked_mul(b.checked_mul(2)? - a)
+            } else {
+                a.checked_mul(a)?.checked_add(b.checked_mul(b)?)
+            }
+        }
+    };
+}
+
+checked_fibonacci!(
+    /// Like `fibonacci`, but returns None instead of overflowing.
+    checked_fibonacci,
+    usize
+);
+
+checked_fibonacci!(
+    /// Fibonacci in 128 bits, exact up to F(186).
+    checked_fibonacci_u128,
+    u128
+);
+
+/// Exact nth fibonacci number for any n, by fast doubling.
+pub fn fibonacci_big(n: usize) -> BigUint {
+    if let Some(&f) = FIB_TABLE.get(n) {
+        return BigUint::from(f);
+    }
+
+    let mut a = BigUint::zero();
+    let mut b = BigUint::one();
+
+    for bit in (0..usize::BITS - n.leading_zeros()).rev() {
+        let two_b = &b + &b;
+        let c = &a * &(&two_b - &a);
+        let d = &(&a * &a) + &(&b * &b);
+        if (n >> bit) & 1 == 0 {
+            a = c;
+            b = d;
+        } else {
+            b = &c + &d;
+            a = d;
+        }
+    }
+
+    a
+}
+
+/// Computes F(n) mod m by fast doubling, in O(log n).
+/// Panics if m is zero.
+pub fn fibonacci_mod(n: u128, m: u64) -> u64 {
+    assert!(m != 0, "modulus must be positive");
+    if n < FIB_TABLE.len() as u128 {
+        return FIB_TABLE[n as usize] % m;
+    }
+
+    let (mut a, mut b) = (0, 1 % m);
+    for bit in (0..u128::BITS - n.leading_zeros()).rev() {
+        // 2F(k+1) - F(k), kept non-negative.
+        let twice_b_minus_a = sub_mod(add_mod(b, b, m), a, m);
+        let c = mul_mod(a, twice_b_minus_a, m);
+        let d = add_mod(mul_mod(a, a, m), mul_mod(b, b, m), m);
+        if (n >> bit) & 1 == 0 {
+            a = c;
+            b = d;
+        } else {
+            a = d;
+            b = add_mod(c, d, m);
+        }
+    }
+
+    a
+}
+
+/// Pisano period: the period of the fibonacci
+/// sequence modulo m. Panics if m is zero.
+pub fn pisano_period(m: u64) -> u128 {
+    assert!(m != 0, "modulus must be positive");
+
+    factorize(m)
+        .into_iter()
+        .map(|(p, k)| prime_power_period(p, k))
+        .fold(1, lcm)
+}
+
+/// Pisano period of p^k for a prime p.
+///
+/// π(p) divides p - 1 when p = ±1 (mod 10), 2(p + 1) when
+/// p = ±3 (mod 10), and π(p^k) divides p^(k-1) π(p). Start
+/// from that multiple and divide out primes while the
+/// result is still a period.
+fn prime_power_period(p: u64, k: u32) -> u128 {
+    let modulus = p.pow(k);
+
+    let mut factors = match p {
+        2 => vec![(3, 1)],
+        5 => vec![(2, 2), (5, 1)],
+        _ if p % 10 == 1 || p % 10 == 9 => factorize(p - 1),
+        // p is odd, so 2(p + 1) = 4 (p / 2 + 1) without overflow.
+        _ => {
+            let mut factors = factorize(p / 2 + 1);
+            factors.push((2, 2));
+            factors
+        }
+    };
+    if k > 1 {
+        factors.push((p, k - 1));
+    }
+
+    let mut period: u128 = factors.iter().map(|&(q, e)| (q as u128).pow(e)).product();
+    let is_period = |n: u128| fibonacci_mod(n, modulus) == 0 && fibonacci_mod(n + 1, modulus) == 1;
+    for &(q, _) in factors.iter() {
+        let q = q as u128;
+        while period.is_multiple_of(q) && is_period(period / q) {
+            period /= q;
+        }
+    }
+
+    period
+}
+
+/// Computes the nth fibonacci number as the top right
+/// entry of [[0, 1], [1, 1]]^n. The power also holds
+/// F(n+1), which must fit in T.
+pub fn fibonacci_matrix<T: Semiring>(n: usize) -> T {
+    let step = Matrix::from_rows(vec![vec![T::zero(), T::one()], vec![T::one(), T::one()]]);
+    step.pow(n as u64)[(0, 1)].clone()
+}
+
+/// Computes the nth fibonacci number in O(n) by
 /// repeating [f(n-1), f(n)] * [[0, 1], [1, 1]].
-#[allow(dead_code)]
-pub fn fibonacci(n: usize) -> usize {
+pub fn fibonacci_linear(n: usize) -> usize {
     let mut state: [usize; 2] = [0, 1];
 
     if n <= 1 {
@@ -15,14 +204,130 @@ pub fn fibonacci(n: usize) -> usize {
     state[1]
 }
 
+/// Lazy terms of a(n) = a(n-1) + ... + a(n-k), where k is
+/// the number of initial terms. Ends before the first
+/// term that would overflow u64.
+#[derive(Debug, Clone)]
+pub struct KBonacci {
+    window: VecDeque<u64>,
+    k: usize,
+}
+
+impl KBonacci {
+    /// Panics if there are no initial terms.
+    pub fn new(initial: Vec<u64>) -> Self {
+        assert!(!initial.is_empty(), "need at least one initial term");
+        KBonacci {
+            k: initial.len(),
+            window: initial.into(),
+        }
+    }
+}
+
+impl Iterator for KBonacci {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        // Once a term overflows the window only drains.
+        if self.window.len() == self.k {
+            let next = self
+                .window
+                .iter()
+                .try_fold(0u64, |sum, &term| sum.checked_add(term));
+            if let Some(next) = next {
+                self.window.push_back(next);
+            }
+        }
+        self.window.pop_front()
+    }
+}
+
+/// 0, 1, 1, 2, 3, 5, 8, ...
+pub fn fibonacci_iter() -> KBonacci {
+    KBonacci::new(vec![0, 1])
+}
+
+/// 2, 1, 3, 4, 7, 11, 18, ...
+pub fn lucas_iter() -> KBonacci {
+    KBonacci::new(vec![2, 1])
+}
+
+/// 0, 0, 1, 1, 2, 4, 7, 13, ...
+pub fn tribonacci_iter() -> KBonacci {
+    k_bonacci_iter(3)
+}
+
+/// k - 1 zeros and a one, then sums of the last k terms.
+/// Panics if k is zero.
+pub fn k_bonacci_iter(k: usize) -> KBonacci {
+    assert!(k > 0, "k must be at least one");
+    let mut initial = vec![0; k];
+    initial[k - 1] = 1;
+    KBonacci::new(initial)
+}
+
+/// Zeckendorf representation of n: the unique set of
+/// non-consecutive fibonacci numbers summing to n,
+/// largest first. Zero is the empty sum.
+pub fn zeckendorf(mut n: u64) -> Vec<u64> {
+    // Greedily taking the largest fibonacci number
+    // never leaves room for the next smaller one.
+    let mut terms = vec![];
+    for &fib in FIB_TABLE[2..].iter().rev() {
+        if fib <= n {
+            terms.push(fib);
+            n -= fib;
+        }
+    }
+
+    terms
+}
+
+/// Inverse of `zeckendorf`. Returns None unless terms are
+/// distinct, non-consecutive fibonacci numbers, largest
+/// first, whose sum fits in u64.
+pub fn from_zeckendorf(terms: &[u64]) -> Option<u64> {
+    let fibs = &FIB_TABLE[2..];
+
+    let mut previous: Option<usize> = None;
+    let mut sum = 0u64;
+    for &term in terms {
+        let index = fibs.binary_search(&term).ok()?;
+        if let Some(previous) = previous {
+            if index + 1 >= previous {
+                return None;
+            }
+        }
+        previous = Some(index);
+        sum = sum.checked_add(term)?;
+    }
+
+    Some(sum)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::fibonacci;
+    use super::*;
+
+    /// Largest n with F(n) < 2^64 and 2^128.
+    const MAX_U64: usize = 93;
+    const MAX_U128: usize = 186;
+
+    #[test]
+    fn test_table() {
+        let expected: Vec<u64> = fibonacci_iter().collect();
+        assert_eq!(FIB_TABLE.to_vec(), expected);
+        assert_eq!(FIB_TABLE.len(), MAX_U64 + 1);
+
+        // Usable in const contexts.
+        const F50: usize = fibonacci(50);
+        assert_eq!(F50, 12_586_269_025);
+    }
 
     #[quickcheck]
     fn test_fib(n: usize) -> bool {
-        if n >= 80 {
-            return true;
+        if n > MAX_U64 {
+            return checked_fibonacci(n).is_none();
         }
 
         let fib_n = fibonacci(n);
@@ -35,4 +340,162 @@ mod tests {
 
         fib_n == n
     }
+
+    #[test]
+    fn test_checked_fib_bounds() {
+        assert_eq!(checked_fibonacci(MAX_U64), Some(fibonacci(MAX_U64)));
+        assert_eq!(checked_fibonacci(MAX_U64 + 1), None);
+        assert_eq!(
+            checked_fibonacci_u128(MAX_U128),
+            Some(332_825_110_087_067_562_321_196_029_789_634_457_848)
+        );
+        assert_eq!(checked_fibonacci_u128(MAX_U128 + 1), None);
+        assert_eq!(checked_fibonacci(usize::MAX), None);
+    }
+
+    #[quickcheck]
+    fn test_fib_u128(n: u8) -> bool {
+        let n = n as usize;
+        match checked_fibonacci_u128(n) {
+            Some(fib_n) => n <= MAX_U128 && BigUint::from(fib_n) == fibonacci_big(n),
+            None => n > MAX_U128,
+        }
+    }
+
+    #[test]
+    fn test_fib_big() {
+        assert_eq!(fibonacci_big(0), BigUint::zero());
+        assert_eq!(fibonacci_big(1), BigUint::one());
+        assert_eq!(
+            fibonacci_big(300).to_string(),
+            "222232244629420445529739893461909967206666939096499764990979600"
+        );
+        let (a, b, c) = (fibonacci_big(998), fibonacci_big(999), fibonacci_big(1000));
+        assert_eq!(&a + &b, c);
+    }
+
+    #[test]
+    fn test_fib_matches_linear() {
+        for n in 0..=MAX_U64 {
+            assert_eq!(fibonacci(n), fibonacci_linear(n));
+        }
+    }
+
+    #[quickcheck]
+    fn test_fib_matrix(n: u8) -> bool {
+        let n = n as usize;
+        let big = fibonacci_matrix::<BigUint>(n) == fibonacci_big(n);
+        if n < MAX_U128 {
+            big && fibonacci_matrix::<u128>(n) == checked_fibonacci_u128(n).unwrap()
+        } else {
+            big
+        }
+    }
+
+    #[quickcheck]
+    fn test_fib_mod(n: u8, m: u64) -> bool {
+        let n = n as usize % (MAX_U128 + 1);
+        let m = m.max(1);
+        let expected = checked_fibonacci_u128(n).unwrap() % m as u128;
+        fibonacci_mod(n as u128, m) as u128 == expected
+    }
+
+    #[test]
+    fn test_fib_mod_huge_n() {
+        let m = 1_000_000_007;
+        // F(n) mod m repeats every π(m) terms.
+        let period = pisano_period(m);
+        let n = u128::MAX - 12345;
+        assert_eq!(fibonacci_mod(n, m), fibonacci_mod(n % period, m));
+        assert_eq!(fibonacci_mod(n, 1), 0);
+        assert_eq!(fibonacci_mod(93, u64::MAX), fibonacci(93) as u64);
+    }
+
+    /// Pisano period by walking the sequence until 0, 1 repeats.
+    fn naive_pisano_period(m: u64) -> u128 {
+        let (mut a, mut b, mut period) = (0, 1 % m, 0);
+        loop {
+            let next = (a + b) % m;
+            a = b;
+            b = next;
+            period += 1;
+            if a == 0 && b == 1 % m {
+                return period;
+            }
+        }
+    }
+
+    #[test]
+    fn test_pisano_period() {
+        for m in 1..=500 {
+            assert_eq!(pisano_period(m), naive_pisano_period(m), "m = {}", m);
+        }
+        assert_eq!(pisano_period(1_000_000_007), 2_000_000_016);
+        assert_eq!(pisano_period(1_000_000_000), 1_500_000_000);
+    }
+
+    #[test]
+    fn test_iterators() {
+        assert!(fibonacci_iter()
+            .take(20)
+            .eq((0..20).map(|n| fibonacci(n) as u64)));
+        assert_eq!(fibonacci_iter().count(), MAX_U64 + 1);
+        assert_eq!(fibonacci_iter().last(), Some(fibonacci(MAX_U64) as u64));
+
+        let lucas: Vec<_> = lucas_iter().take(8).collect();
+        assert_eq!(lucas, vec![2, 1, 3, 4, 7, 11, 18, 29]);
+
+        let tribonacci: Vec<_> = tribonacci_iter().take(10).collect();
+        assert_eq!(tribonacci, vec![0, 0, 1, 1, 2, 4, 7, 13, 24, 44]);
+
+        let tetranacci: Vec<_> = k_bonacci_iter(4).skip(3).take(6).collect();
+        assert_eq!(tetranacci, vec![1, 1, 2, 4, 8, 15]);
+        assert!(k_bonacci_iter(1).take(100).all(|x| x == 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fibonacci_overflow() {
+        fibonacci(FIB_TABLE.len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_k_bonacci_zero() {
+        k_bonacci_iter(0);
+    }
+
+    #[quickcheck]
+    fn test_lucas(n: u8) -> bool {
+        // L(n) = F(n-1) + F(n+1)
+        let n = (n as usize % 90) + 1;
+        lucas_iter().nth(n) == Some((fibonacci(n - 1) + fibonacci(n + 1)) as u64)
+    }
+
+    #[quickcheck]
+    fn test_zeckendorf(n: u64) -> bool {
+        let terms = zeckendorf(n);
+        terms.iter().sum::<u64>() == n && from_zeckendorf(&terms) == Some(n)
+    }
+
+    #[test]
+    fn test_zeckendorf_known() {
+        assert_eq!(zeckendorf(0), vec![]);
+        assert_eq!(zeckendorf(100), vec![89, 8, 3]);
+        assert_eq!(zeckendorf(u64::MAX).iter().sum::<u64>(), u64::MAX);
+
+        assert_eq!(from_zeckendorf(&[89, 8, 3]), Some(100));
+        // Consecutive, unordered, repeated and non-fibonacci terms.
+        assert_eq!(from_zeckendorf(&[8, 5]), None);
+        assert_eq!(from_zeckendorf(&[3, 8]), None);
+        assert_eq!(from_zeckendorf(&[3, 3]), None);
+        assert_eq!(from_zeckendorf(&[4]), None);
+    }
+
+    #[test]
+    fn test_fib_known_values() {
+        assert_eq!(fibonacci(10), 55);
+        assert_eq!(fibonacci(50), 12_586_269_025);
+        assert_eq!(fibonacci(93), 12_200_160_415_121_876_738);
+    }
 }
@@ -0,0 +1,264 @@
+//! Fibonacci universal code for streams of integers.
+//!
+//! A value v is written as the Zeckendorf representation of v + 1,
+//! one bit per fibonacci number from F(2) upwards, followed by an
+//! extra 1. Zeckendorf terms are never consecutive, so "11" only
+//! appears at the end of a codeword and a decoder can always find
+//! the start of the next one after a corrupt bit.
+//!
+//! Bits are packed least significant first. The last byte is
+//! padded with zeros, which never form a codeword.
+use std::error::Error;
+use std::fmt;
+
+use super::fibonacci::{zeckendorf, FIB_TABLE};
+
+/// F(2), F(3), ..., F(93), every fibonacci number above zero
+/// that fits in u64. A codeword has at most one bit for each.
+fn fibs() -> &'static [u64] {
+    &FIB_TABLE[2..]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Codeword starting at bit does not fit in u64.
+    Overflow { bit: usize },
+    /// Stream ends inside the codeword starting at bit.
+    Truncated { bit: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Overflow { bit } => write!(f, "codeword at bit {} overflows", bit),
+            DecodeError::Truncated { bit } => write!(f, "codeword at bit {} is truncated", bit),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+#[derive(Debug, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            self.bytes[self.len / 8] |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+}
+
+/// Encode values as consecutive fibonacci codewords.
+pub fn encode(values: &[u64]) -> Vec<u8> {
+    let fibs = fibs();
+    let mut writer = BitWriter::default();
+
+    for &value in values {
+        // Encode value + 1, so that zero has a codeword.
+        // u64::MAX + 1 needs F(93) plus a remainder below F(92).
+        let terms = match value.checked_add(1) {
+            Some(n) => zeckendorf(n),
+            None => {
+                let top = *fibs.last().unwrap();
+                let mut terms = vec![top];
+                terms.extend(zeckendorf(u64::MAX - top + 1));
+                terms
+            }
+        };
+
+        let mut bits = vec![false; fibs.len()];
+        for term in terms {
+            bits[fibs.binary_search(&term).unwrap()] = true;
+        }
+        let len = bits.iter().rposition(|&bit| bit).unwrap() + 1;
+        for &bit in &bits[..len] {
+            writer.push(bit);
+        }
+        writer.push(true);
+    }
+
+    writer.bytes
+}
+
+/// Iterator over the codewords of a stream.
+///
+/// A corrupt codeword yields an error, after which decoding
+/// resumes at the next "11" terminator. Trailing zero bits
+/// are treated as padding.
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Decoder { bytes, pos: 0 }
+    }
+
+    fn bit(&self, pos: usize) -> Option<bool> {
+        self.bytes
+            .get(pos / 8)
+            .map(|byte| byte >> (pos % 8) & 1 == 1)
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<u64, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let mut sum = 0u128;
+        let mut index = 0;
+        let mut previous = false;
+        let mut seen_one = false;
+
+        loop {
+            let bit = match self.bit(self.pos) {
+                Some(bit) => bit,
+                // Only zeros since the last codeword is padding.
+                None if !seen_one => return None,
+                None => return Some(Err(DecodeError::Truncated { bit: start })),
+            };
+            self.pos += 1;
+
+            if bit && previous {
+                // Terminator. Undo the + 1 from encoding.
+                return Some(if sum - 1 > u64::MAX as u128 {
+                    Err(DecodeError::Overflow { bit: start })
+                } else {
+                    Ok((sum - 1) as u64)
+                });
+            }
+
+            if bit {
+                match fibs().get(index) {
+                    Some(&fib) => sum += fib as u128,
+                    // Too long for u64, wait for the terminator and
+                    // report the whole codeword as one error.
+                    None => sum = u128::MAX,
+                }
+                seen_one = true;
+            }
+            previous = bit;
+            index += 1;
+        }
+    }
+}
+
+/// Decode a whole stream, failing on the first corrupt codeword.
+pub fn decode(bytes: &[u8]) -> Result<Vec<u64>, DecodeError> {
+    Decoder::new(bytes).collect()
+}
+
+/// Decode a whole stream, skipping corrupt codewords.
+pub fn decode_lossy(bytes: &[u8]) -> Vec<u64> {
+    Decoder::new(bytes).filter_map(Result::ok).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[quickcheck]
+    fn test_roundtrip(values: Vec<u64>) -> bool {
+        decode(&encode(&values)) == Ok(values)
+    }
+
+    #[test]
+    fn test_known_codewords() {
+        // 0 -> 1 -> "11", 1 -> 2 -> "011", 2 -> 3 -> "0011", 3 -> 4 -> "1011"
+        assert_eq!(encode(&[0]), vec![0b11]);
+        assert_eq!(encode(&[1]), vec![0b110]);
+        assert_eq!(encode(&[0, 3]), vec![0b11_0111]);
+        assert_eq!(encode(&[]), Vec::<u8>::new());
+
+        let extremes = [u64::MAX, 0, u64::MAX - 1];
+        assert_eq!(decode(&encode(&extremes)), Ok(extremes.to_vec()));
+    }
+
+    #[test]
+    fn test_small_values_are_compact() {
+        let values: Vec<u64> = (0..100).map(|i| i % 8).collect();
+        // At most 6 bits per value below 8, instead of 64.
+        assert!(encode(&values).len() * 8 <= values.len() * 6);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = encode(&[5, 1_000_000]);
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Truncated { bit: 5 })
+        );
+    }
+
+    fn from_bits(bits: &[u8]) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        for &bit in bits {
+            writer.push(bit == 1);
+        }
+        writer.bytes
+    }
+
+    /// Bits used by the codeword of a single value.
+    fn codeword_len(value: u64) -> usize {
+        let bytes = encode(&[value]);
+        let last = bytes.last().unwrap();
+        bytes.len() * 8 - last.leading_zeros() as usize
+    }
+
+    #[test]
+    fn test_overflow() {
+        // 1 0 repeated runs past F(93) without a terminator,
+        // followed by 000011 which encodes 7.
+        let mut bits: Vec<u8> = [1, 0].repeat(60);
+        bits.extend_from_slice(&[1, 1]);
+        bits.extend_from_slice(&[0, 0, 0, 0, 1, 1]);
+
+        let bytes = from_bits(&bits);
+        let mut decoder = Decoder::new(&bytes);
+        assert_eq!(decoder.next(), Some(Err(DecodeError::Overflow { bit: 0 })));
+        assert_eq!(decoder.next(), Some(Ok(7)));
+        assert_eq!(decoder.next(), None);
+        assert_eq!(decode_lossy(&bytes), vec![7]);
+    }
+
+    #[quickcheck]
+    fn test_resynchronizes(values: Vec<u16>, flip: usize) -> bool {
+        // A run of zeros ("11" codewords) can carry a misaligned
+        // terminator along, so only look at nonzero values.
+        let values: Vec<u64> = values.into_iter().map(|v| v as u64 + 1).collect();
+        let mut bytes = encode(&values);
+        if values.is_empty() {
+            return true;
+        }
+
+        let total: usize = values.iter().map(|&v| codeword_len(v)).sum();
+        let flip = flip % total;
+        bytes[flip / 8] ^= 1 << (flip % 8);
+
+        // Find the codeword holding the flipped bit.
+        let mut end = 0;
+        let mut damaged = 0;
+        for (i, &value) in values.iter().enumerate() {
+            end += codeword_len(value);
+            if flip < end {
+                damaged = i;
+                break;
+            }
+        }
+
+        // The flip can at most merge or split the damaged
+        // codeword and its successor. Everything after is intact.
+        let intact = &values[(damaged + 2).min(values.len())..];
+        decode_lossy(&bytes).ends_with(intact)
+    }
+}
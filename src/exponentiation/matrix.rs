@@ -0,0 +1,159 @@
+use std::ops::{Index, IndexMut, Mul};
+
+use super::algebra::{power, Monoid, Semiring};
+
+/// Dense rows x cols matrix stored in row-major order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matrix<T> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+impl<T: Semiring> Matrix<T> {
+    /// Panics unless data holds exactly rows * cols values.
+    pub fn new(rows: usize, cols: usize, data: Vec<T>) -> Self {
+        assert_eq!(data.len(), rows * cols, "matrix data has wrong length");
+        Matrix { rows, cols, data }
+    }
+
+    /// Panics if rows have different lengths.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Self {
+        let cols = rows.first().map_or(0, Vec::len);
+        let n = rows.len();
+        assert!(
+            rows.iter().all(|row| row.len() == cols),
+            "matrix rows have different lengths"
+        );
+        Matrix::new(n, cols, rows.into_iter().flatten().collect())
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Matrix::new(rows, cols, vec![T::zero(); rows * cols])
+    }
+
+    pub fn identity(n: usize) -> Self {
+        let mut matrix = Matrix::zeros(n, n);
+        for i in 0..n {
+            matrix[(i, i)] = T::one();
+        }
+        matrix
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    /// Raise a square matrix to the kth power
+    /// by repeated squaring, in O(n^3 log k).
+    pub fn pow(&self, k: u64) -> Self {
+        assert!(self.is_square(), "only square matrices have powers");
+        power(self, k)
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        assert!(i < self.rows && j < self.cols, "matrix index out of bounds");
+        &self.data[i * self.cols + j]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        assert!(i < self.rows && j < self.cols, "matrix index out of bounds");
+        &mut self.data[i * self.cols + j]
+    }
+}
+
+impl<T: Semiring> Mul for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    /// Panics if the inner dimensions differ.
+    fn mul(self, other: &Matrix<T>) -> Matrix<T> {
+        assert_eq!(self.cols, other.rows, "matrix dimensions do not match");
+
+        let mut result = Matrix::zeros(self.rows, other.cols);
+        for i in 0..self.rows {
+            for j in 0..other.cols {
+                result[(i, j)] = (0..self.cols).fold(T::zero(), |sum, k| {
+                    sum.plus(&self[(i, k)].times(&other[(k, j)]))
+                });
+            }
+        }
+
+        result
+    }
+}
+
+/// Square matrices under multiplication.
+impl<T: Semiring> Monoid for Matrix<T> {
+    fn identity_like(&self) -> Self {
+        Matrix::identity(self.rows)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Matrix;
+
+    fn small(values: Vec<u8>, n: usize) -> Matrix<u64> {
+        let mut data: Vec<u64> = values.into_iter().map(|x| (x % 4) as u64).collect();
+        data.resize(n * n, 0);
+        Matrix::new(n, n, data)
+    }
+
+    #[quickcheck]
+    fn test_pow(values: Vec<u8>, k: u8) -> bool {
+        let matrix = small(values, 3);
+        let k = k % 12;
+
+        let mut expected = Matrix::identity(3);
+        for _ in 0..k {
+            expected = &expected * &matrix;
+        }
+
+        matrix.pow(k as u64) == expected
+    }
+
+    #[quickcheck]
+    fn test_identity(values: Vec<u8>) -> bool {
+        let matrix = small(values, 4);
+        let identity = Matrix::identity(4);
+        &matrix * &identity == matrix && &identity * &matrix == matrix
+    }
+
+    #[test]
+    fn test_rectangular_mul() {
+        let a = Matrix::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let b = Matrix::from_rows(vec![vec![7], vec![8], vec![9]]);
+        assert_eq!(&a * &b, Matrix::from_rows(vec![vec![50], vec![122]]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mismatched_mul() {
+        let a = Matrix::<i32>::zeros(2, 3);
+        let _ = &a * &a;
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_non_square_pow() {
+        Matrix::<i32>::zeros(2, 3).pow(2);
+    }
+}
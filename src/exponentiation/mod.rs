@@ -1 +1,8 @@
+pub mod algebra;
+pub mod cli;
 pub mod fibonacci;
+pub mod fibonacci_code;
+pub mod matrix;
+pub mod modular;
+pub mod polynomial;
+pub mod recurrence;
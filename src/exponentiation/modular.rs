@@ -0,0 +1,387 @@
+use std::convert::TryFrom;
+
+/// Computes a * b mod m, widening to 128 bits
+/// so the product cannot overflow.
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+/// Computes a + b mod m, widening to 128 bits
+/// so the sum cannot overflow.
+pub fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 + b as u128) % m as u128) as u64
+}
+
+/// k^0, k^1, ..., k^(N-1). Fails to compile, or panics at
+/// runtime, if k^(N-1) overflows u64.
+pub const fn power_table<const N: usize>(k: u64) -> [u64; N] {
+    let mut table = [1; N];
+    let mut i = 1;
+    while i < N {
+        table[i] = table[i - 1] * k;
+        i += 1;
+    }
+    table
+}
+
+/// Every power of 2, 3 and 10 that fits in u64.
+pub const POWERS_OF_2: [u64; 64] = power_table(2);
+pub const POWERS_OF_3: [u64; 41] = power_table(3);
+pub const POWERS_OF_10: [u64; 20] = power_table(10);
+
+/// base^exp, or None on overflow. Bases with a
+/// power table are looked up instead of multiplied.
+pub fn checked_pow(base: u64, exp: u64) -> Option<u64> {
+    let table: &[u64] = match base {
+        2 => &POWERS_OF_2,
+        3 => &POWERS_OF_3,
+        10 => &POWERS_OF_10,
+        _ => {
+            return u32::try_from(exp)
+                .ok()
+                .and_then(|exp| base.checked_pow(exp))
+        }
+    };
+    usize::try_from(exp)
+        .ok()
+        .and_then(|exp| table.get(exp).copied())
+}
+
+/// Computes base^exp mod m by repeated squaring.
+/// Panics if m is zero.
+pub fn pow_mod(base: u64, mut exp: u64, m: u64) -> u64 {
+    assert!(m != 0, "modulus must be positive");
+
+    let mut result = 1 % m;
+    let mut base = base % m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+
+    result
+}
+
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Least common multiple, in u128 as the lcm of periods
+/// modulo a u64 can exceed u64. Panics if both are zero.
+pub fn lcm(a: u128, b: u128) -> u128 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        let r = x % y;
+        x = y;
+        y = r;
+    }
+    a / x * b
+}
+
+/// Computes a - b mod m for b at most m.
+pub fn sub_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 + m as u128 - b as u128) % m as u128) as u64
+}
+
+/// Returns (g, x, y) with a * x + b * y = g = gcd(a, b).
+pub fn extended_gcd(a: u64, b: u64) -> (u64, i128, i128) {
+    let (mut old_r, mut r) = (a as i128, b as i128);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    (old_r as u64, old_x, old_y)
+}
+
+/// Inverse of a modulo m, if a and m are coprime.
+pub fn mod_inverse(a: u64, m: u64) -> Option<u64> {
+    assert!(m != 0, "modulus must be positive");
+
+    let (g, x, _) = extended_gcd(a % m, m);
+    if g != 1 {
+        return None;
+    }
+    Some(x.rem_euclid(m as i128) as u64 % m)
+}
+
+/// Chinese Remainder Theorem. Given pairs (r, m) for x = r (mod m),
+/// returns (x, lcm) with the smallest such x, or None if the
+/// congruences disagree or the lcm of moduli overflows u64.
+/// Moduli need not be coprime.
+pub fn crt(congruences: &[(u64, u64)]) -> Option<(u64, u64)> {
+    let (mut x, mut lcm) = (0u128, 1u128);
+    for &(r, m) in congruences {
+        assert!(m != 0, "modulus must be positive");
+        let (r, m) = (r as u128 % m as u128, m as u128);
+
+        // Solve x + lcm * t = r (mod m) for t.
+        let g = gcd(lcm as u64, m as u64) as u128;
+        let diff = (r + m - x % m) % m;
+        if diff % g != 0 {
+            return None;
+        }
+        let step = m / g;
+        let inverse = mod_inverse((lcm / g % step) as u64, step as u64)? as u128;
+        let t = mul_mod((diff / g % step) as u64, inverse as u64, step as u64) as u128;
+
+        x += lcm * t;
+        lcm *= step;
+        if lcm > u64::MAX as u128 {
+            return None;
+        }
+    }
+
+    Some((x as u64, lcm as u64))
+}
+
+/// Deterministic Miller-Rabin. These bases have no
+/// strong pseudoprimes below 3.3 * 10^24 > 2^64.
+pub fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    for &p in BASES.iter() {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    // n - 1 = d * 2^s with d odd.
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    BASES.iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Nontrivial factor of a composite n by Pollard's rho
+/// with Floyd cycle detection. Panics if n is prime or below 4.
+pub fn pollard_rho(n: u64) -> u64 {
+    assert!(n >= 4 && !is_prime(n), "n must be composite");
+
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+
+    // Retry with another polynomial x^2 + c when the cycle closes on n.
+    for c in 1.. {
+        let f = |x: u64| ((mul_mod(x, x, n) as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = f(x);
+            y = f(f(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+
+    unreachable!()
+}
+
+/// Prime factors of n with their multiplicities,
+/// in increasing order. Small primes are divided out
+/// first, the rest is split with Pollard's rho.
+pub fn factorize(mut n: u64) -> Vec<(u64, u32)> {
+    let mut primes = vec![];
+    for p in 2..1000 {
+        while n.is_multiple_of(p) {
+            n /= p;
+            primes.push(p);
+        }
+    }
+
+    let mut composites = if n > 1 { vec![n] } else { vec![] };
+    while let Some(m) = composites.pop() {
+        if is_prime(m) {
+            primes.push(m);
+        } else {
+            let d = pollard_rho(m);
+            composites.push(d);
+            composites.push(m / d);
+        }
+    }
+
+    primes.sort_unstable();
+    let mut factors: Vec<(u64, u32)> = vec![];
+    for p in primes {
+        match factors.last_mut() {
+            Some((q, k)) if *q == p => *k += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[quickcheck]
+    fn test_pow_mod_small(base: u8, exp: u8, m: u8) -> bool {
+        let m = m as u64 + 1;
+        let expected = (0..exp).fold(1 % m, |acc, _| acc * base as u64 % m);
+        pow_mod(base as u64, exp as u64, m) == expected
+    }
+
+    #[quickcheck]
+    fn test_pow_mod_large(base: u64) -> bool {
+        // Fermat's little theorem for the Mersenne prime 2^61 - 1.
+        let p = (1 << 61) - 1;
+        base.is_multiple_of(p) || pow_mod(base, p - 1, p) == 1
+    }
+
+    #[test]
+    fn test_pow_mod_edges() {
+        assert_eq!(pow_mod(0, 0, 7), 1);
+        assert_eq!(pow_mod(5, 0, 1), 0);
+        assert_eq!(pow_mod(u64::MAX, u64::MAX, u64::MAX), 0);
+        assert_eq!(pow_mod(u64::MAX - 1, 2, u64::MAX), 1);
+    }
+
+    #[test]
+    fn test_power_tables() {
+        let tables: [(u64, &[u64]); 3] =
+            [(2, &POWERS_OF_2), (3, &POWERS_OF_3), (10, &POWERS_OF_10)];
+        for &(k, table) in tables.iter() {
+            for (i, &power) in table.iter().enumerate() {
+                assert_eq!(Some(power), k.checked_pow(i as u32));
+            }
+            // The tables stop right before overflow.
+            assert_eq!(k.checked_pow(table.len() as u32), None);
+        }
+    }
+
+    #[quickcheck]
+    fn test_checked_pow(base: u8, exp: u8) -> bool {
+        let (base, exp) = (base as u64 % 12, exp as u64 % 70);
+        checked_pow(base, exp) == base.checked_pow(exp as u32)
+    }
+
+    #[quickcheck]
+    fn test_factorize(n: u64) -> bool {
+        let n = n.max(1);
+        let factors = factorize(n);
+        factors.iter().map(|&(p, k)| p.pow(k)).product::<u64>() == n
+            && factors.iter().all(|&(p, _)| is_prime(p))
+            && factors.windows(2).all(|pair| pair[0].0 < pair[1].0)
+    }
+
+    #[test]
+    fn test_factorize_large() {
+        let (p, q) = (4_294_967_291, 4_294_967_279);
+        assert_eq!(factorize(p * q), vec![(q, 1), (p, 1)]);
+        assert_eq!(factorize(1 << 63), vec![(2, 63)]);
+        assert_eq!(factorize(1), vec![]);
+    }
+
+    fn naive_is_prime(n: u64) -> bool {
+        n >= 2
+            && (2..n)
+                .take_while(|p| p * p <= n)
+                .all(|p| !n.is_multiple_of(p))
+    }
+
+    #[test]
+    fn test_is_prime() {
+        for n in 0..10_000 {
+            assert_eq!(is_prime(n), naive_is_prime(n), "n = {}", n);
+        }
+
+        // Strong pseudoprimes to several small bases.
+        for &n in [3_215_031_751, 2_152_302_898_747, 3_825_123_056_546_413_051].iter() {
+            assert!(!is_prime(n), "n = {}", n);
+        }
+        assert!(is_prime(18_446_744_073_709_551_557));
+        assert!(!is_prime(u64::MAX));
+    }
+
+    #[quickcheck]
+    fn test_pollard_rho(a: u32, b: u32) -> bool {
+        let n = (a as u64 | 2) * (b as u64 | 2);
+        let d = pollard_rho(n);
+        d > 1 && d < n && n.is_multiple_of(d)
+    }
+
+    #[quickcheck]
+    fn test_lcm(a: u32, b: u32) -> bool {
+        let (a, b) = (a as u128 + 1, b as u128 + 1);
+        let l = lcm(a, b);
+        l.is_multiple_of(a) && l.is_multiple_of(b) && l * gcd(a as u64, b as u64) as u128 == a * b
+    }
+
+    #[quickcheck]
+    fn test_add_sub_mod(a: u64, b: u64, m: u64) -> bool {
+        let m = m.max(1);
+        let (a, b) = (a % m, b % m);
+        sub_mod(add_mod(a, b, m), b, m) == a
+    }
+
+    #[quickcheck]
+    fn test_extended_gcd(a: u64, b: u64) -> bool {
+        let (g, x, y) = extended_gcd(a, b);
+        g == gcd(a, b) && a as i128 * x + b as i128 * y == g as i128
+    }
+
+    #[quickcheck]
+    fn test_mod_inverse(a: u64, m: u64) -> bool {
+        let m = m.max(1);
+        match mod_inverse(a, m) {
+            Some(inverse) => mul_mod(a, inverse, m) == 1 % m,
+            None => gcd(a, m) != 1,
+        }
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        // Moduli sharing a factor.
+        assert_eq!(crt(&[(1, 4), (3, 6)]), Some((9, 12)));
+        assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+        assert_eq!(crt(&[]), Some((0, 1)));
+        assert_eq!(crt(&[(1, 1 << 40), (0, (1 << 40) - 1)]), None);
+    }
+
+    #[quickcheck]
+    fn test_crt_solution(x: u64, moduli: Vec<u16>) -> bool {
+        let congruences: Vec<_> = moduli
+            .iter()
+            .take(3)
+            .map(|&m| {
+                let m = m as u64 + 1;
+                (x % m, m)
+            })
+            .collect();
+
+        match crt(&congruences) {
+            Some((y, lcm)) => y < lcm && congruences.iter().all(|&(r, m)| y % m == r),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use std::ops::{Add, Mul, Sub};
+
+use super::modular::{add_mod, mod_inverse, mul_mod, pow_mod, sub_mod};
+
+/// Products where both factors have at least this many
+/// coefficients are computed with the number-theoretic transform.
+const NTT_THRESHOLD: usize = 64;
+
+/// Primes of the form c * 2^k + 1 with primitive root 3.
+/// Each supports transforms of length up to 2^23.
+const NTT_PRIMES: [u64; 3] = [998_244_353, 167_772_161, 469_762_049];
+
+const NTT_MAX_LEN: usize = 1 << 23;
+
+/// Divisors with at least this many coefficients are divided
+/// through a power series inverse instead of long division.
+const NEWTON_THRESHOLD: usize = 64;
+
+/// Polynomial with coefficients modulo m, lowest degree first.
+/// Coefficients are reduced and there are no trailing zeros,
+/// so the zero polynomial has no coefficients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polynomial {
+    coefficients: Vec<u64>,
+    modulus: u64,
+}
+
+impl Polynomial {
+    /// Panics if modulus is zero.
+    pub fn new(coefficients: Vec<u64>, modulus: u64) -> Self {
+        assert!(modulus != 0, "modulus must be positive");
+        let coefficients = coefficients.into_iter().map(|c| c % modulus).collect();
+        Polynomial {
+            coefficients,
+            modulus,
+        }
+        .normalize()
+    }
+
+    pub fn zero(modulus: u64) -> Self {
+        Polynomial::new(vec![], modulus)
+    }
+
+    pub fn one(modulus: u64) -> Self {
+        Polynomial::new(vec![1], modulus)
+    }
+
+    /// x^degree.
+    pub fn monomial(degree: usize, modulus: u64) -> Self {
+        let mut coefficients = vec![0; degree + 1];
+        coefficients[degree] = 1;
+        Polynomial::new(coefficients, modulus)
+    }
+
+    pub fn coefficients(&self) -> &[u64] {
+        &self.coefficients
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// Degree, or None for the zero polynomial.
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    /// Value at x, by Horner's rule.
+    pub fn evaluate(&self, x: u64) -> u64 {
+        let m = self.modulus;
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0, |acc, &c| add_mod(mul_mod(acc, x, m), c, m))
+    }
+
+    /// Quotient and remainder, with the remainder of lower
+    /// degree than divisor. Panics if the leading coefficient
+    /// of divisor is not invertible modulo m.
+    pub fn div_rem(&self, divisor: &Polynomial) -> (Polynomial, Polynomial) {
+        Divider::new(divisor).div_rem(self)
+    }
+
+    /// self^n mod divisor, by repeated squaring.
+    /// Panics like `div_rem`.
+    pub fn pow_mod(&self, mut n: u64, divisor: &Polynomial) -> Polynomial {
+        let divider = Divider::new(divisor);
+        let mut result = divider.div_rem(&Polynomial::one(self.modulus)).1;
+        let mut base = divider.div_rem(self).1;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = divider.div_rem(&(&result * &base)).1;
+            }
+            n >>= 1;
+            if n > 0 {
+                base = divider.div_rem(&(&base * &base)).1;
+            }
+        }
+        result
+    }
+
+    fn normalize(mut self) -> Self {
+        while self.coefficients.last() == Some(&0) {
+            self.coefficients.pop();
+        }
+        self
+    }
+
+    /// self mod x^len.
+    fn truncate(&self, len: usize) -> Self {
+        let len = len.min(self.coefficients.len());
+        Polynomial::new(self.coefficients[..len].to_vec(), self.modulus)
+    }
+
+    /// Coefficients in reverse order, as a polynomial of degree len - 1.
+    fn reverse(&self, len: usize) -> Self {
+        let mut coefficients = self.coefficients.clone();
+        coefficients.resize(len, 0);
+        coefficients.reverse();
+        Polynomial::new(coefficients, self.modulus)
+    }
+
+    /// Power series g with self * g = 1 mod x^len, by Newton's
+    /// iteration g' = g (2 - self g). The constant term must be one.
+    fn inverse_series(&self, len: usize) -> Self {
+        debug_assert_eq!(self.coefficients.first(), Some(&(1 % self.modulus)));
+
+        let two = Polynomial::new(vec![2], self.modulus);
+        let mut inverse = Polynomial::one(self.modulus);
+        let mut precision = 1;
+        while precision < len {
+            precision *= 2;
+            let error = &self.truncate(precision) * &inverse;
+            inverse = (&inverse * &(&two - &error.truncate(precision))).truncate(precision);
+        }
+        inverse.truncate(len)
+    }
+}
+
+/// Divides repeatedly by one divisor, computing the power
+/// series inverse of the reversed divisor once.
+struct Divider<'a> {
+    divisor: &'a Polynomial,
+    /// Inverse of the leading coefficient.
+    lead_inverse: u64,
+    /// Inverse of the reversed monic divisor, grown on demand.
+    inverse: Option<Polynomial>,
+}
+
+impl<'a> Divider<'a> {
+    fn new(divisor: &'a Polynomial) -> Self {
+        let m = divisor.modulus;
+        let lead = *divisor
+            .coefficients
+            .last()
+            .expect("division by zero polynomial");
+        let lead_inverse = mod_inverse(lead, m).expect("leading coefficient must be invertible");
+
+        // Only needed, and only affordable, for large divisors.
+        let inverse = if divisor.coefficients.len() >= NEWTON_THRESHOLD {
+            let monic = divisor * &Polynomial::new(vec![lead_inverse], m);
+            let k = monic.coefficients.len();
+            Some(monic.reverse(k).inverse_series(k))
+        } else {
+            None
+        };
+
+        Divider {
+            divisor,
+            lead_inverse,
+            inverse,
+        }
+    }
+
+    fn div_rem(&self, dividend: &Polynomial) -> (Polynomial, Polynomial) {
+        let m = self.divisor.modulus;
+        assert_eq!(m, dividend.modulus, "polynomial moduli differ");
+
+        let n = dividend.coefficients.len();
+        let k = self.divisor.coefficients.len();
+        if n < k {
+            return (Polynomial::zero(m), dividend.clone());
+        }
+
+        let quotient = match &self.inverse {
+            // rev(q) = rev(a) / rev(b) mod x^(n - k + 1).
+            Some(inverse) => {
+                let len = n - k + 1;
+                let inverse = if inverse.coefficients.len() >= len {
+                    inverse.truncate(len)
+                } else {
+                    let lead = Polynomial::new(vec![self.lead_inverse], m);
+                    (self.divisor * &lead).reverse(k).inverse_series(len)
+                };
+                let reversed = (&dividend.reverse(n).truncate(len) * &inverse).truncate(len);
+                &reversed.reverse(len) * &Polynomial::new(vec![self.lead_inverse], m)
+            }
+            None => return self.long_division(dividend),
+        };
+
+        let remainder = dividend - &(&quotient * self.divisor);
+        (quotient, remainder)
+    }
+
+    fn long_division(&self, dividend: &Polynomial) -> (Polynomial, Polynomial) {
+        let m = self.divisor.modulus;
+        let divisor = &self.divisor.coefficients;
+        let k = divisor.len();
+
+        let mut rem = dividend.coefficients.clone();
+        let mut quotient = vec![0; rem.len() - k + 1];
+        for d in (k - 1..rem.len()).rev() {
+            let q = mul_mod(rem[d], self.lead_inverse, m);
+            quotient[d + 1 - k] = q;
+            for (i, &c) in divisor.iter().enumerate() {
+                let target = d + 1 - k + i;
+                rem[target] = sub_mod(rem[target], mul_mod(q, c, m), m);
+            }
+        }
+        rem.truncate(k - 1);
+
+        (Polynomial::new(quotient, m), Polynomial::new(rem, m))
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: &Polynomial) -> Polynomial {
+        assert_eq!(self.modulus, other.modulus, "polynomial moduli differ");
+        let m = self.modulus;
+        let len = self.coefficients.len().max(other.coefficients.len());
+        let coefficients = (0..len)
+            .map(|i| {
+                let a = *self.coefficients.get(i).unwrap_or(&0);
+                let b = *other.coefficients.get(i).unwrap_or(&0);
+                add_mod(a, b, m)
+            })
+            .collect();
+        Polynomial::new(coefficients, m)
+    }
+}
+
+impl Sub for &Polynomial {
+    type Output = Polynomial;
+
+    fn sub(self, other: &Polynomial) -> Polynomial {
+        assert_eq!(self.modulus, other.modulus, "polynomial moduli differ");
+        let m = self.modulus;
+        let len = self.coefficients.len().max(other.coefficients.len());
+        let coefficients = (0..len)
+            .map(|i| {
+                let a = *self.coefficients.get(i).unwrap_or(&0);
+                let b = *other.coefficients.get(i).unwrap_or(&0);
+                sub_mod(a, b, m)
+            })
+            .collect();
+        Polynomial::new(coefficients, m)
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        assert_eq!(self.modulus, other.modulus, "polynomial moduli differ");
+        let (a, b, m) = (&self.coefficients, &other.coefficients, self.modulus);
+        if a.is_empty() || b.is_empty() {
+            return Polynomial::zero(m);
+        }
+
+        let coefficients = if a.len().min(b.len()) >= NTT_THRESHOLD && fits_ntt(a, b, m) {
+            ntt_mul(a, b, m)
+        } else {
+            schoolbook_mul(a, b, m)
+        };
+        Polynomial::new(coefficients, m)
+    }
+}
+
+fn schoolbook_mul(a: &[u64], b: &[u64], m: u64) -> Vec<u64> {
+    let mut product = vec![0; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            product[i + j] = add_mod(product[i + j], mul_mod(x, y, m), m);
+        }
+    }
+    product
+}
+
+/// Whether the transform can compute a * b exactly: either m is
+/// one of the transform primes, or every coefficient of the
+/// integer product is below the product of all three.
+fn fits_ntt(a: &[u64], b: &[u64], m: u64) -> bool {
+    if a.len() + b.len() - 1 > NTT_MAX_LEN {
+        return false;
+    }
+    if NTT_PRIMES.contains(&m) {
+        return true;
+    }
+
+    let bound: u128 = NTT_PRIMES.iter().map(|&p| p as u128).product();
+    let max = (m - 1) as u128;
+    max.checked_mul(max)
+        .and_then(|square| square.checked_mul(a.len().min(b.len()) as u128))
+        .is_some_and(|largest| largest < bound)
+}
+
+/// In-place transform modulo p. The length must be a power of two.
+fn ntt(values: &mut [u64], inverse: bool, p: u64) {
+    let n = values.len();
+
+    // Bit reversal permutation.
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let mut root = pow_mod(3, (p - 1) / len as u64, p);
+        if inverse {
+            root = pow_mod(root, p - 2, p);
+        }
+        for chunk in values.chunks_mut(len) {
+            let (low, high) = chunk.split_at_mut(len / 2);
+            let mut w = 1;
+            for (u, v) in low.iter_mut().zip(high.iter_mut()) {
+                let t = mul_mod(*v, w, p);
+                *v = sub_mod(*u, t, p);
+                *u = add_mod(*u, t, p);
+                w = mul_mod(w, root, p);
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        let n_inverse = pow_mod(n as u64, p - 2, p);
+        for value in values.iter_mut() {
+            *value = mul_mod(*value, n_inverse, p);
+        }
+    }
+}
+
+/// a * b modulo a transform prime p.
+fn ntt_mul_prime(a: &[u64], b: &[u64], p: u64) -> Vec<u64> {
+    let len = a.len() + b.len() - 1;
+    let n = len.next_power_of_two();
+
+    let mut fa: Vec<u64> = a.iter().map(|&x| x % p).collect();
+    let mut fb: Vec<u64> = b.iter().map(|&x| x % p).collect();
+    fa.resize(n, 0);
+    fb.resize(n, 0);
+    ntt(&mut fa, false, p);
+    ntt(&mut fb, false, p);
+    for (x, &y) in fa.iter_mut().zip(fb.iter()) {
+        *x = mul_mod(*x, y, p);
+    }
+    ntt(&mut fa, true, p);
+
+    fa.truncate(len);
+    fa
+}
+
+/// a * b modulo m, through one transform prime when m is one,
+/// otherwise through all three combined with Garner's algorithm.
+fn ntt_mul(a: &[u64], b: &[u64], m: u64) -> Vec<u64> {
+    if NTT_PRIMES.contains(&m) {
+        return ntt_mul_prime(a, b, m);
+    }
+
+    let [p0, p1, p2] = NTT_PRIMES;
+    let r0 = ntt_mul_prime(a, b, p0);
+    let r1 = ntt_mul_prime(a, b, p1);
+    let r2 = ntt_mul_prime(a, b, p2);
+
+    let p0_inverse = mod_inverse(p0, p1).unwrap();
+    let p0p1_inverse = mod_inverse(mul_mod(p0, p1, p2), p2).unwrap();
+    (0..r0.len())
+        .map(|i| {
+            // x = r0 + p0 t1 + p0 p1 t2, exact below p0 p1 p2.
+            let t1 = mul_mod(sub_mod(r1[i], r0[i] % p1, p1), p0_inverse, p1);
+            let partial = (r0[i] + p0 * t1) % p2;
+            let t2 = mul_mod(sub_mod(r2[i], partial, p2), p0p1_inverse, p2);
+            let x = r0[i] as u128 + p0 as u128 * t1 as u128 + (p0 * p1) as u128 * t2 as u128;
+            (x % m as u128) as u64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P: u64 = 998_244_353;
+
+    fn poly(coefficients: Vec<u64>, m: u64) -> Polynomial {
+        Polynomial::new(coefficients, m)
+    }
+
+    #[quickcheck]
+    fn test_add_sub(a: Vec<u64>, b: Vec<u64>, m: u64) -> bool {
+        let m = m.max(1);
+        let (a, b) = (poly(a, m), poly(b, m));
+        &(&a + &b) - &b == a
+    }
+
+    #[quickcheck]
+    fn test_mul_evaluates(a: Vec<u64>, b: Vec<u64>, x: u64, m: u64) -> bool {
+        let m = m.max(1);
+        let (a, b) = (poly(a, m), poly(b, m));
+        (&a * &b).evaluate(x) == mul_mod(a.evaluate(x), b.evaluate(x), m)
+    }
+
+    #[quickcheck]
+    fn test_ntt_matches_schoolbook(a: Vec<u64>, b: Vec<u64>, m: u32) -> bool {
+        let m = m as u64 + 1;
+        let mut a: Vec<u64> = a.iter().map(|x| x % m).collect();
+        let mut b: Vec<u64> = b.iter().map(|x| x % m).collect();
+        a.resize(a.len().max(NTT_THRESHOLD), 1);
+        b.resize(b.len().max(NTT_THRESHOLD), m - 1);
+        fits_ntt(&a, &b, m) && ntt_mul(&a, &b, m) == schoolbook_mul(&a, &b, m)
+    }
+
+    #[test]
+    fn test_ntt_large() {
+        // Large enough that the product needs all three primes.
+        let m = (1 << 31) - 1;
+        let a: Vec<u64> = (0..3000).map(|i| m - 1 - i).collect();
+        let b: Vec<u64> = (0..2000).map(|i| i * i % m).collect();
+        assert_eq!(ntt_mul(&a, &b, m), schoolbook_mul(&a, &b, m));
+        assert_eq!(ntt_mul(&a, &b, P), schoolbook_mul(&a, &b, P));
+        assert!(!fits_ntt(&a, &b, u64::MAX));
+    }
+
+    #[quickcheck]
+    fn test_div_rem(a: Vec<u64>, b: Vec<u64>, lead: u64) -> bool {
+        // Monic divisors are invertible for every modulus.
+        let m = 1_000_000_007;
+        let mut b = b;
+        b.push(lead % (m - 1) + 1);
+        let (a, b) = (poly(a, m), poly(b, m));
+
+        let (q, r) = a.div_rem(&b);
+        &(&q * &b) + &r == a && r.degree() < b.degree()
+    }
+
+    #[test]
+    fn test_div_rem_newton() {
+        // Divisors past the threshold take the power series path.
+        let m = 1_000_000_007;
+        let a = poly((0..500).map(|i| i * 7919 + 3).collect(), m);
+        let b = poly((0..150).map(|i| i * i + 2).collect(), m);
+
+        let (q, r) = a.div_rem(&b);
+        assert_eq!(&(&q * &b) + &r, a);
+        assert!(r.degree() < b.degree());
+        assert_eq!(q.degree(), Some(500 - 150));
+    }
+
+    #[test]
+    fn test_div_rem_small() {
+        // x^2 - 1 = (x + 1)(x - 1) + 0, and x^3 + 2 mod x^2 = 2.
+        let m = 7;
+        let (q, r) = poly(vec![6, 0, 1], m).div_rem(&poly(vec![1, 1], m));
+        assert_eq!((q, r), (poly(vec![6, 1], m), Polynomial::zero(m)));
+        let (_, r) = poly(vec![2, 0, 0, 1], m).div_rem(&Polynomial::monomial(2, m));
+        assert_eq!(r, poly(vec![2], m));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_div_rem_not_invertible() {
+        poly(vec![1, 2, 3], 6).div_rem(&poly(vec![1, 2], 6));
+    }
+
+    #[quickcheck]
+    fn test_pow_mod(a: Vec<u64>, p: Vec<u64>, n: u8) -> bool {
+        let m = 1_000_000_007;
+        let mut p = p;
+        p.truncate(8);
+        p.push(1);
+        let (a, p) = (poly(a, m), poly(p, m));
+
+        let mut expected = Polynomial::one(m).div_rem(&p).1;
+        for _ in 0..n {
+            expected = (&expected * &a).div_rem(&p).1;
+        }
+        a.pow_mod(n as u64, &p) == expected
+    }
+
+    #[test]
+    fn test_pow_mod_fibonacci() {
+        // x^n mod x^2 - x - 1 = F(n) x + F(n-1).
+        let m = P;
+        let p = poly(vec![m - 1, m - 1, 1], m);
+        let r = Polynomial::monomial(1, m).pow_mod(90, &p);
+        assert_eq!(
+            r.coefficients(),
+            &[1_779_979_416_004_714_189 % m, 2_880_067_194_370_816_120 % m]
+        );
+    }
+}
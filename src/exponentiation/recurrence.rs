@@ -0,0 +1,266 @@
+use super::algebra::Semiring;
+use super::matrix::Matrix;
+use super::modular::{add_mod, mul_mod, pow_mod};
+use super::polynomial::Polynomial;
+
+/// Sequence defined modulo m by its first k terms and
+///
+/// a(n) = c[0] a(n-1) + c[1] a(n-2) + ... + c[k-1] a(n-k)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinearRecurrence {
+    coefficients: Vec<u64>,
+    initial: Vec<u64>,
+    modulus: u64,
+}
+
+impl LinearRecurrence {
+    /// Panics if there is not one initial term per
+    /// coefficient, or if modulus is zero.
+    pub fn new(coefficients: Vec<u64>, initial: Vec<u64>, modulus: u64) -> Self {
+        assert!(modulus != 0, "modulus must be positive");
+        assert_eq!(
+            coefficients.len(),
+            initial.len(),
+            "need one initial term per coefficient"
+        );
+
+        let reduce = |xs: Vec<u64>| xs.into_iter().map(|x| x % modulus).collect();
+        LinearRecurrence {
+            coefficients: reduce(coefficients),
+            initial: reduce(initial),
+            modulus,
+        }
+    }
+
+    /// Shortest recurrence generating sequence modulo
+    /// a prime p, found by Berlekamp-Massey.
+    pub fn from_sequence(sequence: &[u64], p: u64) -> Self {
+        let coefficients = berlekamp_massey(sequence, p);
+        let initial = sequence[..coefficients.len()].to_vec();
+        LinearRecurrence::new(coefficients, initial, p)
+    }
+
+    pub fn coefficients(&self) -> &[u64] {
+        &self.coefficients
+    }
+
+    pub fn order(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// nth term by Kitamasa's method, in O(M(k) log n) where
+    /// M(k) is the cost of multiplying degree k polynomials.
+    ///
+    /// Finds x^n mod the characteristic polynomial,
+    /// r(x) = sum r_i x^i, then a(n) = sum r_i a(i).
+    pub fn nth(&self, n: u64) -> u64 {
+        let k = self.order();
+        if n < k as u64 {
+            return self.initial[n as usize];
+        }
+        // Every polynomial is zero modulo one.
+        let m = self.modulus;
+        if m == 1 {
+            return 0;
+        }
+
+        let r = Polynomial::monomial(1, m).pow_mod(n, &self.characteristic());
+        r.coefficients()
+            .iter()
+            .zip(self.initial.iter())
+            .fold(0, |sum, (&r, &a)| add_mod(sum, mul_mod(r, a, m), m))
+    }
+
+    /// x^k - c[0] x^(k-1) - ... - c[k-1]. Monic, so it can
+    /// be divided by for any modulus.
+    pub fn characteristic(&self) -> Polynomial {
+        let m = self.modulus;
+        let mut coefficients: Vec<u64> = self
+            .coefficients
+            .iter()
+            .rev()
+            .map(|&c| (m - c) % m)
+            .collect();
+        coefficients.push(1);
+        Polynomial::new(coefficients, m)
+    }
+}
+
+/// Coefficients of the shortest linear recurrence that
+/// generates sequence modulo a prime p, in the order
+/// used by `LinearRecurrence`.
+pub fn berlekamp_massey(sequence: &[u64], p: u64) -> Vec<u64> {
+    assert!(p > 1, "modulus must be prime");
+
+    // Connection polynomials: current and the one before
+    // the last length change, with that step's discrepancy.
+    let mut current = vec![1];
+    let mut previous = vec![1];
+    let mut previous_discrepancy = 1;
+    let mut length = 0;
+    let mut shift = 1;
+
+    for n in 0..sequence.len() {
+        let discrepancy = (1..=length).fold(sequence[n] % p, |d, i| {
+            (d + mul_mod(current[i], sequence[n - i] % p, p)) % p
+        });
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let scale = mul_mod(discrepancy, pow_mod(previous_discrepancy, p - 2, p), p);
+        let old = current.clone();
+        if current.len() < previous.len() + shift {
+            current.resize(previous.len() + shift, 0);
+        }
+        for (i, &b) in previous.iter().enumerate() {
+            current[i + shift] = (current[i + shift] + p - mul_mod(scale, b, p)) % p;
+        }
+
+        if 2 * length <= n {
+            length = n + 1 - length;
+            previous = old;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+
+    current.resize(length + 1, 0);
+    current[1..].iter().map(|&c| (p - c) % p).collect()
+}
+
+/// Exact nth term over any semiring, as the first entry of
+/// C^n * initial for the k x k companion matrix C.
+///
+/// C^n holds terms up to a(n + k - 1), which must fit in T.
+pub fn nth_term<T: Semiring>(coefficients: &[T], initial: &[T], n: u64) -> T {
+    let k = coefficients.len();
+    assert_eq!(k, initial.len(), "need one initial term per coefficient");
+    if n < k as u64 {
+        return initial[n as usize].clone();
+    }
+    if k == 0 {
+        return T::zero();
+    }
+
+    // Shift the window by one and append the next term.
+    let mut companion = Matrix::zeros(k, k);
+    for i in 0..k - 1 {
+        companion[(i, i + 1)] = T::one();
+    }
+    for (j, c) in coefficients.iter().rev().enumerate() {
+        companion[(k - 1, j)] = c.clone();
+    }
+
+    let power = companion.pow(n);
+    (0..k).fold(T::zero(), |sum, j| {
+        sum.plus(&power[(0, j)].times(&initial[j]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fibonacci::{fibonacci_big, fibonacci_mod};
+    use super::*;
+    use crate::bignum::BigUint;
+
+    const P: u64 = 1_000_000_007;
+
+    /// First len terms by direct iteration.
+    fn naive(coefficients: &[u64], initial: &[u64], len: usize, m: u64) -> Vec<u64> {
+        let mut terms = initial.to_vec();
+        while terms.len() < len {
+            let n = terms.len();
+            let next = coefficients.iter().enumerate().fold(0, |sum, (i, &c)| {
+                (sum + mul_mod(c, terms[n - 1 - i], m)) % m
+            });
+            terms.push(next);
+        }
+        terms.truncate(len);
+        terms
+    }
+
+    #[quickcheck]
+    fn test_nth(coefficients: Vec<u64>, seed: u64, m: u32) -> bool {
+        let m = m as u64 + 1;
+        let coefficients: Vec<_> = coefficients.into_iter().take(6).collect();
+        let initial: Vec<_> = (0..coefficients.len() as u64).map(|i| seed ^ i).collect();
+        let recurrence = LinearRecurrence::new(coefficients.clone(), initial.clone(), m);
+
+        let reduced: Vec<_> = coefficients.iter().map(|c| c % m).collect();
+        let initial: Vec<_> = initial.iter().map(|a| a % m).collect();
+        naive(&reduced, &initial, 40, m)
+            .iter()
+            .enumerate()
+            .all(|(n, &term)| recurrence.nth(n as u64) == term)
+    }
+
+    #[test]
+    fn test_fibonacci() {
+        let fibonacci = LinearRecurrence::new(vec![1, 1], vec![0, 1], P);
+        for &n in [0, 1, 2, 10, 1_000_000, u64::MAX].iter() {
+            assert_eq!(fibonacci.nth(n), fibonacci_mod(n as u128, P));
+        }
+
+        let one = BigUint::one();
+        let zero = BigUint::zero();
+        assert_eq!(
+            nth_term(&[one.clone(), one.clone()], &[zero, one], 500),
+            fibonacci_big(500)
+        );
+    }
+
+    #[test]
+    fn test_nth_large_order() {
+        // Past the polynomial thresholds, so the transform and
+        // power series division are both used.
+        let k = 100;
+        let coefficients: Vec<u64> = (0..k).map(|i| i * i + 1).collect();
+        let initial: Vec<u64> = (0..k).map(|i| 3 * i + 7).collect();
+        let recurrence = LinearRecurrence::new(coefficients.clone(), initial.clone(), P);
+
+        let terms = naive(&coefficients, &initial, 3 * k as usize, P);
+        for (n, &term) in terms.iter().enumerate().skip(k as usize) {
+            assert_eq!(recurrence.nth(n as u64), term, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn test_nth_term() {
+        // Tribonacci: 0, 0, 1, 1, 2, 4, 7, 13, 24, 44
+        assert_eq!(nth_term(&[1u64, 1, 1], &[0, 0, 1], 9), 44);
+        assert_eq!(nth_term(&[1u64, 1, 1], &[0, 0, 1], 1), 0);
+        // a(n) = 2 a(n-1): powers of two.
+        assert_eq!(nth_term(&[2u64], &[1], 63), 1 << 63);
+        assert_eq!(nth_term::<u64>(&[], &[], 5), 0);
+    }
+
+    #[quickcheck]
+    fn test_berlekamp_massey(coefficients: Vec<u64>, initial: Vec<u64>) -> bool {
+        let k = coefficients.len().min(initial.len()).min(8);
+        let coefficients: Vec<_> = coefficients[..k].iter().map(|c| c % P).collect();
+        let initial: Vec<_> = initial[..k].iter().map(|a| a % P).collect();
+        let sequence = naive(&coefficients, &initial, 2 * k + 10, P);
+
+        // The inferred recurrence must be no longer and
+        // reproduce every term it was given.
+        let found = LinearRecurrence::from_sequence(&sequence, P);
+        found.order() <= k
+            && sequence
+                .iter()
+                .enumerate()
+                .all(|(n, &term)| found.nth(n as u64) == term)
+    }
+
+    #[test]
+    fn test_berlekamp_massey_known() {
+        assert_eq!(berlekamp_massey(&[0, 1, 1, 2, 3, 5, 8, 13], P), vec![1, 1]);
+        assert_eq!(berlekamp_massey(&[1, 2, 4, 8, 16], P), vec![2]);
+        assert_eq!(berlekamp_massey(&[0, 0, 0], P), Vec::<u64>::new());
+        // 1, -1, 1, -1, ... has a(n) = -a(n-1).
+        assert_eq!(berlekamp_massey(&[1, P - 1, 1, P - 1], P), vec![P - 1]);
+    }
+}
@@ -0,0 +1,89 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    /// An operation on path failed.
+    Io { path: PathBuf, source: io::Error },
+    /// A directory was expected at path.
+    NotADirectory(PathBuf),
+    /// A followed symlink leads back to a directory above it.
+    SymlinkLoop(PathBuf),
+    /// A glob pattern could not be parsed.
+    InvalidPattern(String),
+    /// A sync would delete count entries, over the maximum.
+    TooManyDeletions { count: usize, max: usize },
+    /// The backup dir of a sync is inside its src.
+    BackupInSource(PathBuf),
+}
+
+impl Error {
+    pub fn io<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        Error::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    /// Path the error is about, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. } => Some(path),
+            Error::NotADirectory(path) => Some(path),
+            Error::SymlinkLoop(path) => Some(path),
+            Error::BackupInSource(path) => Some(path),
+            Error::InvalidPattern(_) | Error::TooManyDeletions { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::NotADirectory(path) => write!(f, "{}: not a directory", path.display()),
+            Error::SymlinkLoop(path) => write!(f, "{}: symlink loop", path.display()),
+            Error::InvalidPattern(pattern) => write!(f, "invalid pattern: {}", pattern),
+            Error::TooManyDeletions { count, max } => write!(
+                f,
+                "refusing to delete {} entries, more than the maximum of {}",
+                count, max
+            ),
+            Error::BackupInSource(path) => {
+                write!(f, "{}: backup dir is inside src", path.display())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::NotADirectory(_)
+            | Error::SymlinkLoop(_)
+            | Error::InvalidPattern(_)
+            | Error::TooManyDeletions { .. }
+            | Error::BackupInSource(_) => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Result of a scan that kept going past failing entries.
+/// Each skipped entry left a warning.
+#[derive(Debug)]
+pub struct Outcome<T> {
+    pub value: T,
+    pub warnings: Vec<Error>,
+}
+
+impl<T> Outcome<T> {
+    /// Whether every entry was processed.
+    pub fn is_complete(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
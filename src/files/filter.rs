@@ -0,0 +1,485 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use super::error::{Error, Result};
+
+/// Ignore files read in every directory, later ones
+/// taking precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Shell style pattern over paths relative to the scan root,
+/// separated by `/`. `*` and `?` stop at `/`, `**` crosses
+/// directories, `[a-z]` and `[!a-z]` are classes and `\`
+/// escapes the next character.
+///
+/// As in .gitignore, a pattern without a slash matches the
+/// name at any depth, a leading or inner slash anchors it to
+/// the root, and a trailing slash only matches directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: Vec<char>,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let invalid = || Error::InvalidPattern(pattern.to_string());
+
+        let (body, dir_only) = match pattern.strip_suffix('/') {
+            Some(body) => (body, true),
+            None => (pattern, false),
+        };
+        let anchored = body.contains('/');
+        let body = body.strip_prefix('/').unwrap_or(body);
+        if body.is_empty() {
+            return Err(invalid());
+        }
+
+        let chars: Vec<char> = body.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            i += match chars[i] {
+                '\\' if i + 1 < chars.len() => 2,
+                '\\' => return Err(invalid()),
+                '[' => class(&chars[i..]).ok_or_else(invalid)?.len,
+                _ => 1,
+            };
+        }
+
+        Ok(Glob {
+            pattern: chars,
+            anchored,
+            dir_only,
+        })
+    }
+
+    /// Whether path, relative to the root, matches.
+    pub fn is_match(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let text = if self.anchored {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        let text: Vec<char> = text.chars().collect();
+        glob_match(&self.pattern, &text)
+    }
+}
+
+impl FromStr for Glob {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Glob::new(s)
+    }
+}
+
+/// Character class like `[a-z_]` or `[!0-9]`.
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+    /// Characters taken up in the pattern.
+    len: usize,
+}
+
+impl Class {
+    fn contains(&self, c: char) -> bool {
+        let inside = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        c != '/' && inside != self.negated
+    }
+}
+
+/// Parse the class at the start of p, if it is closed.
+fn class(p: &[char]) -> Option<Class> {
+    let mut i = 1;
+    let negated = matches!(p.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = vec![];
+    // A ] right after the opening bracket is literal.
+    let mut first = true;
+    loop {
+        let c = *p.get(i)?;
+        if c == ']' && !first {
+            return Some(Class {
+                ranges,
+                negated,
+                len: i + 1,
+            });
+        }
+        first = false;
+        if p.get(i + 1) == Some(&'-') && p.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, p[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn glob_match(p: &[char], t: &[char]) -> bool {
+    match p.first() {
+        None => t.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            let rest = &p[2..];
+            match rest.split_first() {
+                // Zero or more whole directories.
+                Some(('/', rest)) => {
+                    glob_match(rest, t)
+                        || (0..t.len()).any(|i| t[i] == '/' && glob_match(rest, &t[i + 1..]))
+                }
+                _ => (0..=t.len()).any(|i| glob_match(rest, &t[i..])),
+            }
+        }
+        Some('*') => {
+            let rest = &p[1..];
+            for i in 0..=t.len() {
+                if glob_match(rest, &t[i..]) {
+                    return true;
+                }
+                if t.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => t.first().is_some_and(|&c| c != '/') && glob_match(&p[1..], &t[1..]),
+        Some('[') => {
+            // Validated when the glob was built.
+            let class = class(p).unwrap();
+            t.first().is_some_and(|&c| class.contains(c)) && glob_match(&p[class.len..], &t[1..])
+        }
+        Some('\\') => t.first() == p.get(1) && glob_match(&p[2..], &t[1..]),
+        Some(c) => t.first() == Some(c) && glob_match(&p[1..], &t[1..]),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    negated: bool,
+}
+
+/// Rules from the ignore files of one directory,
+/// at base relative to the root.
+#[derive(Debug, Clone)]
+pub(crate) struct IgnoreFile {
+    base: String,
+    rules: Vec<Rule>,
+}
+
+/// Rules of a .gitignore style file. Lines that are
+/// not valid patterns are skipped.
+fn parse_ignore(text: &str) -> Vec<Rule> {
+    text.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (line, negated) = match line.strip_prefix('!') {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            // \# and \! escape the prefixes above.
+            let line = match line.strip_prefix('\\') {
+                Some(rest) if rest.starts_with('#') || rest.starts_with('!') => rest,
+                _ => line,
+            };
+            let glob = Glob::new(line).ok()?;
+            Some(Rule { glob, negated })
+        })
+        .collect()
+}
+
+/// Which entries a scan visits, shared by `largest_files` and `sync`.
+///
+/// Directories are only pruned by exclude patterns, ignore
+/// files and markers. Every other condition applies to files alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// When not empty, only files matching one of these are kept.
+    pub include: Vec<Glob>,
+    /// Files and whole directories matching any of these are skipped.
+    pub exclude: Vec<Glob>,
+    /// Honour .gitignore and .ignore files and skip .git directories.
+    pub ignore_files: bool,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Only keep files modified at or after this time.
+    pub modified_after: Option<SystemTime>,
+    /// Only keep files modified before this time.
+    pub modified_before: Option<SystemTime>,
+    /// Directories holding an entry of this name are
+    /// skipped with everything in them.
+    pub skip_marked: Option<String>,
+}
+
+impl Filter {
+    /// Whether to descend into the directory dir, at rel.
+    pub(crate) fn enters(&self, dir: &Path, rel: &str, ignores: &[IgnoreFile]) -> bool {
+        !self.excluded(rel, true, ignores)
+            && self
+                .skip_marked
+                .as_ref()
+                .is_none_or(|marker| fs::symlink_metadata(dir.join(marker)).is_err())
+    }
+
+    /// Whether to keep the file at rel.
+    pub(crate) fn keeps(&self, rel: &str, metadata: &fs::Metadata, ignores: &[IgnoreFile]) -> bool {
+        if self.excluded(rel, false, ignores) {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|g| g.is_match(rel, false)) {
+            return false;
+        }
+
+        let len = metadata.len();
+        if self.min_size.is_some_and(|min| len < min) || self.max_size.is_some_and(|max| len > max)
+        {
+            return false;
+        }
+
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let modified = match metadata.modified() {
+                Ok(modified) => modified,
+                Err(_) => return false,
+            };
+            if self.modified_after.is_some_and(|after| modified < after)
+                || self
+                    .modified_before
+                    .is_some_and(|before| modified >= before)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// The conditions on paths alone, which a file
+    /// still meets after being copied or touched.
+    pub(crate) fn by_path(&self) -> Filter {
+        Filter {
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            modified_before: None,
+            ..self.clone()
+        }
+    }
+
+    fn excluded(&self, rel: &str, is_dir: bool, ignores: &[IgnoreFile]) -> bool {
+        if self.exclude.iter().any(|g| g.is_match(rel, is_dir)) {
+            return true;
+        }
+        if !self.ignore_files {
+            return false;
+        }
+        if is_dir && rel.rsplit('/').next() == Some(".git") {
+            return true;
+        }
+
+        // Rules are applied from the root down, so the last
+        // match, in the deepest ignore file, decides.
+        let mut ignored = false;
+        for file in ignores {
+            let sub = if file.base.is_empty() {
+                rel
+            } else {
+                match rel
+                    .strip_prefix(file.base.as_str())
+                    .and_then(|r| r.strip_prefix('/'))
+                {
+                    Some(sub) => sub,
+                    None => continue,
+                }
+            };
+            for rule in file.rules.iter() {
+                if rule.glob.is_match(sub, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+
+    /// Rules of the ignore files in dir, which is at rel from
+    /// the root. None when disabled or there are no rules.
+    pub(crate) fn load_ignores(&self, dir: &Path, rel: &str) -> Result<Option<IgnoreFile>> {
+        if !self.ignore_files {
+            return Ok(None);
+        }
+
+        let mut rules = vec![];
+        for name in IGNORE_FILES.iter() {
+            let path = dir.join(name);
+            match fs::read_to_string(&path) {
+                Ok(text) => rules.extend(parse_ignore(&text)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::io(path, e)),
+            }
+        }
+
+        Ok(if rules.is_empty() {
+            None
+        } else {
+            Some(IgnoreFile {
+                base: rel.to_string(),
+                rules,
+            })
+        })
+    }
+}
+
+/// rel of a child named name inside the directory at rel.
+pub(crate) fn join(rel: &str, name: &str) -> String {
+    if rel.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", rel, name)
+    }
+}
+
+/// Parse a size like 512, 10K, 1.5MiB or 2G. Units are binary.
+pub fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let invalid = || format!("invalid size: {}", s);
+
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit.trim_end_matches("ib").trim_end_matches('b');
+
+    let shift = match unit {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return Err(invalid()),
+    };
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// Parse an age like 30s, 15m, 12h, 7d or 2w.
+pub fn parse_age(s: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("invalid age: {}", s);
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(path, false)
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(is_match("*.rs", "src/files/mod.rs"));
+        assert!(!is_match("*.rs", "main.rs.bak"));
+        assert!(is_match("src/*.rs", "src/main.rs"));
+        assert!(!is_match("src/*.rs", "src/files/mod.rs"));
+        assert!(!is_match("/main.rs", "src/main.rs"));
+        assert!(is_match("/main.rs", "main.rs"));
+        assert!(is_match("src/**/*.rs", "src/main.rs"));
+        assert!(is_match("src/**/*.rs", "src/files/mod.rs"));
+        assert!(is_match(
+            "**/test_files/*",
+            "src/files/test_files/file1.txt"
+        ));
+        assert!(is_match("file?.txt", "file1.txt"));
+        assert!(!is_match("file?.txt", "file10.txt"));
+        assert!(is_match("file[0-3].txt", "file2.txt"));
+        assert!(!is_match("file[!0-3].txt", "file2.txt"));
+        assert!(is_match("[]]", "]"));
+        assert!(is_match("\\*", "*"));
+        assert!(!is_match("\\*", "a"));
+
+        let target = Glob::new("target/").unwrap();
+        assert!(target.is_match("target", true));
+        assert!(target.is_match("sub/target", true));
+        assert!(!target.is_match("target", false));
+
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("abc\\").is_err());
+        assert!(Glob::new("/").is_err());
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let filter = Filter {
+            ignore_files: true,
+            ..Filter::default()
+        };
+        let ignores = vec![
+            IgnoreFile {
+                base: String::new(),
+                rules: parse_ignore("# build output\ntarget/\n*.log\n!keep.log\n\n\\#notes\n"),
+            },
+            IgnoreFile {
+                base: "sub".to_string(),
+                rules: parse_ignore("/local.txt\n!*.log\n"),
+            },
+        ];
+
+        assert!(!filter.enters(Path::new("target"), "target", &ignores));
+        assert!(!filter.enters(Path::new("sub/target"), "sub/target", &ignores));
+        assert!(!filter.enters(Path::new(".git"), ".git", &ignores));
+        assert!(filter.enters(Path::new("src"), "src", &ignores));
+        assert!(filter.excluded("debug.log", false, &ignores));
+        assert!(!filter.excluded("keep.log", false, &ignores));
+        assert!(filter.excluded("#notes", false, &ignores));
+        // The deeper file re-includes logs and anchors to sub.
+        assert!(!filter.excluded("sub/debug.log", false, &ignores));
+        assert!(filter.excluded("sub/local.txt", false, &ignores));
+        assert!(!filter.excluded("local.txt", false, &ignores));
+        assert!(!filter.excluded("sub/deeper/local.txt", false, &ignores));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5MiB"), Ok(3 << 19));
+        assert_eq!(parse_size("2gb"), Ok(2 << 30));
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("K").is_err());
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_age("7d"), Ok(Duration::from_secs(7 * 86_400)));
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("7y").is_err());
+    }
+}
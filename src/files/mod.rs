@@ -1,55 +1,644 @@
+mod error;
+pub mod filter;
+pub mod report;
+mod top;
+pub mod walk;
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
-use std::io::Result;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub use error::{Error, Outcome, Result};
+pub use filter::{Filter, Glob};
+pub use walk::{Entry, Kind, Symlinks, Traversal};
+
+use top::TopK;
+use walk::FileId;
+
+/// Which kinds of nodes `largest_files` reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Select {
+    Files,
+    Dirs,
+    All,
+}
+
+impl FromStr for Select {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "files" => Ok(Select::Files),
+            "dirs" => Ok(Select::Dirs),
+            "all" => Ok(Select::All),
+            _ => Err(format!("unknown selection: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Number of nodes to return.
+    pub n: usize,
+    /// Deepest level reported, where children of the
+    /// root are at depth 1. None reports the whole tree.
+    /// Directory sizes always include everything below.
+    pub depth: Option<usize>,
+    pub select: Select,
+    pub filter: Filter,
+    pub traversal: Traversal,
+    /// Count a file with several hard links only once,
+    /// at the first of its paths.
+    pub dedup_hardlinks: bool,
+}
+
+impl Options {
+    /// n largest files and directories anywhere under the root.
+    pub fn new(n: usize) -> Self {
+        Options {
+            n,
+            depth: None,
+            select: Select::All,
+            filter: Filter::default(),
+            traversal: Traversal::default(),
+            dedup_hardlinks: true,
+        }
+    }
+}
+
+/// File, directory or link found by `largest_files`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub path: PathBuf,
+    /// File length, or the total length of every
+    /// file below a directory.
+    pub size: u64,
+    pub kind: Kind,
+    pub depth: usize,
+}
+
+/// Orders nodes by size, then by reverse path so that
+/// the first of two equally large paths ranks higher.
+#[derive(Debug)]
+struct BySize(Node);
+
+impl PartialEq for BySize {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BySize {}
+
+impl PartialOrd for BySize {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySize {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .size
+            .cmp(&other.0.size)
+            .then_with(|| other.0.path.cmp(&self.0.path))
+    }
+}
+
+/// Return the n biggest nodes under the src directory,
+/// largest first. Ties are broken by path.
+///
+/// The tree is read by `walk::walk`, then each entry is
+/// ranked once and only the n largest are kept, so ranking
+/// N entries is O(N log n).
+///
+/// Entries that cannot be read are skipped with a warning,
+/// so directory sizes only count what could be read.
+/// Filtered out entries are not counted either, nor are
+/// the second and later paths of a hard linked file.
+pub fn largest_files<P: AsRef<Path>>(src: P, options: &Options) -> Result<Outcome<Vec<Node>>> {
+    let outcome = walk::walk(src.as_ref(), &options.filter, &options.traversal, None)?;
+
+    let mut scan = Scan {
+        options,
+        top: TopK::new(options.n),
+        seen: HashSet::new(),
+    };
+    scan.dir(&outcome.value, src.as_ref(), 1);
+
+    Ok(Outcome {
+        value: scan
+            .top
+            .into_sorted_vec()
+            .into_iter()
+            .map(|ranked| ranked.0)
+            .collect(),
+        warnings: outcome.warnings,
+    })
+}
+
+/// State of ranking a walked tree.
+struct Scan<'a> {
+    options: &'a Options,
+    top: TopK<BySize>,
+    /// Hard linked files counted so far.
+    seen: HashSet<FileId>,
+}
+
+impl Scan<'_> {
+    /// Rank the entries of the directory dir, which sit at depth.
+    /// Returns the total size of the files kept among and below them.
+    fn dir(&mut self, entries: &[Entry], dir: &Path, depth: usize) -> u64 {
+        let mut total = 0;
+        for entry in entries {
+            let kind = entry.kind;
+            let path = dir.join(&entry.name);
+            let size = match &entry.children {
+                Some(children) => self.dir(children, &path, depth + 1),
+                None if kind == Kind::Dir => 0,
+                None => {
+                    // Followed links may lead to a file counted elsewhere.
+                    let shared =
+                        entry.hard_links > 1 || self.options.traversal.symlinks == Symlinks::Follow;
+                    if self.options.dedup_hardlinks && shared {
+                        if let Some(id) = entry.id {
+                            if !self.seen.insert(id) {
+                                continue;
+                            }
+                        }
+                    }
+                    entry.len
+                }
+            };
+            total += size;
+
+            let wanted = match self.options.select {
+                Select::Files => kind != Kind::Dir,
+                Select::Dirs => kind == Kind::Dir,
+                Select::All => true,
+            };
+            if wanted && self.options.depth.is_none_or(|max| depth <= max) {
+                self.top.push(BySize(Node {
+                    path,
+                    size,
+                    kind,
+                    depth,
+                }));
+            }
+        }
+        total
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncOptions {
+    pub filter: Filter,
+    /// Followed links are copied as their targets,
+    /// recorded links are recreated in dest.
+    pub traversal: Traversal,
+    /// Remove dest entries missing from src. Entries that the
+    /// patterns and ignore files of the filter leave out are
+    /// never removed; size and age only limit what is copied.
+    pub delete: bool,
+    /// Refuse to sync, changing nothing, when more entries
+    /// than this would be deleted.
+    pub max_deletions: Option<usize>,
+    /// Dest entries never deleted, matched like excludes.
+    pub protect: Vec<Glob>,
+    /// Where deleted and overwritten entries are moved, under
+    /// their path from dest, with a numbered suffix if an
+    /// earlier backup is there. It must not be inside src.
+    /// Nothing inside it, or inside the backup dirs of earlier
+    /// syncs, is walked or deleted.
+    pub backup_dir: Option<PathBuf>,
+}
+
+/// What `sync` changed in dest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    /// Files and links written.
+    pub copied: usize,
+    /// Entries removed or moved to the backup dir, counting
+    /// everything below a removed directory.
+    pub deleted: usize,
+}
+
+/// Mirror the tree under src into dest, creating missing
+/// directories and copying new and changed files at any
+/// depth. Entries that fail are skipped with a warning.
+///
+/// In delete mode, extraneous dest entries are removed
+/// first, after checking how many there are.
+pub fn sync<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dest: Q,
+    options: &SyncOptions,
+) -> Result<Outcome<SyncSummary>> {
+    let (src, dest) = (src.as_ref(), dest.as_ref());
+    if let Some(backup) = &options.backup_dir {
+        // Backups would be copied back on the next sync.
+        if rel_within(src, backup).is_some() {
+            return Err(Error::BackupInSource(backup.clone()));
+        }
+        if backup.is_dir() {
+            mark(backup)?;
+        }
+    }
+    let outcome = walk::walk(src, &options.filter, &options.traversal, None)?;
+
+    let mut mirror = Mirror {
+        options,
+        backup: None,
+        summary: SyncSummary::default(),
+        warnings: outcome.warnings,
+    };
+    if options.delete && dest.is_dir() {
+        mirror.backup = options
+            .backup_dir
+            .as_ref()
+            .and_then(|backup| rel_within(dest, backup));
+        mirror.delete(&outcome.value, src, dest)?;
+    }
+    fs::create_dir_all(dest).map_err(|e| Error::io(dest, e))?;
+    mirror.sync_dir(&outcome.value, src, dest, "");
+
+    Ok(Outcome {
+        value: mirror.summary,
+        warnings: mirror.warnings,
+    })
+}
+
+/// Name of the empty file that marks a backup dir.
+const BACKUP_MARKER: &str = ".sync-backup";
+
+/// State of a `sync`.
+struct Mirror<'a> {
+    options: &'a SyncOptions,
+    /// Path of the backup dir from dest, if it is inside.
+    backup: Option<String>,
+    summary: SyncSummary,
+    warnings: Vec<Error>,
+}
+
+impl Mirror<'_> {
+    /// Remove the entries of dest that are not in the tree
+    /// under src, unless there are too many of them.
+    fn delete(&mut self, entries: &[Entry], src: &Path, dest: &Path) -> Result<()> {
+        // A file that is too old or small in src can be newer
+        // or bigger in dest, so both sides are matched by path.
+        let filter = self.options.filter.by_path();
+        let walked;
+        let entries = if filter == self.options.filter {
+            entries
+        } else {
+            walked = walk::walk(src, &filter, &self.options.traversal, None)?.value;
+            &walked
+        };
+        // Links are removed, never followed.
+        let traversal = Traversal {
+            symlinks: Symlinks::Record,
+            ..self.options.traversal
+        };
+        let filter = Filter {
+            skip_marked: Some(BACKUP_MARKER.to_string()),
+            ..filter
+        };
+        let outcome = walk::walk(dest, &filter, &traversal, None)?;
+        self.warnings.extend(outcome.warnings);
+
+        let mut kinds = HashMap::new();
+        index(entries, "", &mut kinds);
+        let mut doomed = vec![];
+        let count = self.plan(&outcome.value, dest, "", &kinds, &mut doomed);
+        if let Some(max) = self.options.max_deletions {
+            if count > max {
+                return Err(Error::TooManyDeletions { count, max });
+            }
+        }
+
+        for (path, rel, count) in doomed {
+            match self.discard(&path, &rel) {
+                Ok(()) => self.summary.deleted += count,
+                Err(e) => self.warnings.push(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Collect the paths and rels of the entries to delete from
+    /// the dest directory dir, with the number of entries each
+    /// one holds. Returns their total.
+    fn plan(
+        &self,
+        entries: &[Entry],
+        dir: &Path,
+        rel: &str,
+        src: &HashMap<String, bool>,
+        doomed: &mut Vec<(PathBuf, String, usize)>,
+    ) -> usize {
+        let mut total = 0;
+        for entry in entries {
+            let path = dir.join(&entry.name);
+            let rel = filter::join(rel, &entry.name.to_string_lossy());
+            if self.protected(entry, &rel) {
+                continue;
+            }
+            let is_dir = entry.kind == Kind::Dir;
+            // A directory where src has a file is in the way.
+            let extraneous = match src.get(&rel) {
+                Some(&src_is_dir) => is_dir && !src_is_dir,
+                None => true,
+            };
+            if extraneous && !self.guarded(entry, &rel) {
+                let count = count(entry);
+                doomed.push((path, rel, count));
+                total += count;
+            } else if let Some(children) = &entry.children {
+                total += self.plan(children, &path, &rel, src, doomed);
+            }
+        }
+        total
+    }
+
+    /// Whether entry, at rel, must stay with everything below it.
+    fn protected(&self, entry: &Entry, rel: &str) -> bool {
+        let is_dir = entry.kind == Kind::Dir;
+        self.options
+            .protect
+            .iter()
+            .any(|glob| glob.is_match(rel, is_dir))
+            || self
+                .backup
+                .as_ref()
+                .is_some_and(|backup| backup.is_empty() || rel == backup || is_below(rel, backup))
+    }
+
+    /// Whether entry, at rel, or anything below it must stay.
+    fn guarded(&self, entry: &Entry, rel: &str) -> bool {
+        // The backup dir may not have been made yet.
+        self.protected(entry, rel)
+            || self
+                .backup
+                .as_ref()
+                .is_some_and(|backup| is_below(backup, rel))
+            || entry.children.iter().flatten().any(|child| {
+                let name = child.name.to_string_lossy();
+                self.guarded(child, &filter::join(rel, &name))
+            })
+    }
 
-/// Return n biggest nodes within
-/// src directory.
-pub fn largest_files(src: &str, n: usize) -> Result<Vec<fs::DirEntry>> {
-    // Do not proceed unless path leads to a directory.
-    if !fs::metadata(src)?.is_dir() {
-        return Ok(vec![]);
+    /// Move path, at rel from dest, into the backup
+    /// dir, or remove it if there is none.
+    fn discard(&self, path: &Path, rel: &str) -> Result<()> {
+        match &self.options.backup_dir {
+            Some(backup) => {
+                mark(backup)?;
+                move_to(path, &backup.join(rel))
+            }
+            None => remove(path),
+        }
+    }
+
+    /// Sync the entries of the src directory, at rel,
+    /// into the directory dest.
+    fn sync_dir(&mut self, entries: &[Entry], src: &Path, dest: &Path, rel: &str) {
+        for entry in entries {
+            let src = src.join(&entry.name);
+            let dest = dest.join(&entry.name);
+            let rel = filter::join(rel, &entry.name.to_string_lossy());
+            let result = match &entry.children {
+                Some(children) => match self.make_dir(&dest, &rel) {
+                    Ok(()) => {
+                        self.sync_dir(children, &src, &dest, &rel);
+                        continue;
+                    }
+                    Err(e) => Err(e),
+                },
+                None if entry.kind == Kind::Symlink => self.sync_symlink(&src, &dest, &rel),
+                None => self.sync_file(entry, &src, &dest, &rel),
+            };
+            match result {
+                Ok(true) => self.summary.copied += 1,
+                Ok(false) => {}
+                Err(e) => self.warnings.push(e),
+            }
+        }
     }
 
-    let mut entries: Vec<_> = fs::read_dir(src).unwrap().map(|r| r.unwrap()).collect();
-    entries.sort_by(|file1, file2| {
-        let size1 = fs::metadata(file1.path()).unwrap().len();
-        let size2 = fs::metadata(file2.path()).unwrap().len();
-        return size2.partial_cmp(&size1).unwrap();
-    });
-    entries.truncate(n);
+    /// Make dest a directory, replacing a file or link there.
+    fn make_dir(&self, dest: &Path, rel: &str) -> Result<()> {
+        match fs::symlink_metadata(dest) {
+            Ok(metadata) if metadata.is_dir() => return Ok(()),
+            Ok(_) => self.discard(dest, rel)?,
+            Err(_) => {}
+        }
+        fs::create_dir(dest).map_err(|e| Error::io(dest, e))
+    }
 
-    Ok(entries)
+    /// Copy entry, at src and rel, over dest unless both hold
+    /// the same bytes. Returns whether dest was written.
+    fn sync_file(&self, entry: &Entry, src: &Path, dest: &Path, rel: &str) -> Result<bool> {
+        match fs::symlink_metadata(dest) {
+            Ok(metadata) if metadata.is_dir() => {
+                let e = io::Error::new(io::ErrorKind::AlreadyExists, "a directory is in the way");
+                return Err(Error::io(dest, e));
+            }
+            Ok(metadata) if metadata.is_file() => {
+                if entry.len == metadata.len()
+                    && fs::read(src).map_err(|e| Error::io(src, e))?
+                        == fs::read(dest).map_err(|e| Error::io(dest, e))?
+                {
+                    return Ok(false);
+                }
+                if self.options.backup_dir.is_some() {
+                    self.discard(dest, rel)?;
+                }
+            }
+            // Copying through a link would write where it points.
+            Ok(_) => self.discard(dest, rel)?,
+            Err(_) => {}
+        }
+        fs::copy(src, dest).map_err(|e| Error::io(src, e))?;
+        Ok(true)
+    }
+
+    /// Recreate the link src, at rel, as dest, unless it already
+    /// points to the same target. Returns whether dest was written.
+    fn sync_symlink(&self, src: &Path, dest: &Path, rel: &str) -> Result<bool> {
+        let target = fs::read_link(src).map_err(|e| Error::io(src, e))?;
+        match fs::symlink_metadata(dest) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                if fs::read_link(dest).ok().as_ref() == Some(&target) {
+                    return Ok(false);
+                }
+                self.discard(dest, rel)?;
+            }
+            Ok(metadata) if !metadata.is_dir() => self.discard(dest, rel)?,
+            _ => {}
+        }
+        walk::symlink(&target, dest).map_err(|e| Error::io(dest, e))?;
+        Ok(true)
+    }
 }
 
-pub fn sync(src: &str, dest: &str) {
-    let entries: Vec<_> = fs::read_dir(src).unwrap().map(|r| r.unwrap()).collect();
+/// Map the path from the root of every entry below rel
+/// to whether it is a directory.
+fn index(entries: &[Entry], rel: &str, kinds: &mut HashMap<String, bool>) {
     for entry in entries {
-        let src_sub_path = entry.path();
-        let dest_sub_path = Path::new(dest).join(entry.file_name());
-        let src_is_dir = src_sub_path.is_dir();
-        let dest_is_dir = dest_sub_path.is_dir();
+        let rel = filter::join(rel, &entry.name.to_string_lossy());
+        index(entry.children.as_deref().unwrap_or_default(), &rel, kinds);
+        kinds.insert(rel, entry.kind == Kind::Dir);
+    }
+}
+
+/// Create the backup dir, marked so that later syncs
+/// leave it alone whatever backup dir they use.
+fn mark(backup: &Path) -> Result<()> {
+    let marker = backup.join(BACKUP_MARKER);
+    if fs::symlink_metadata(&marker).is_err() {
+        fs::create_dir_all(backup).map_err(|e| Error::io(backup, e))?;
+        fs::write(&marker, "").map_err(|e| Error::io(&marker, e))?;
+    }
+    Ok(())
+}
+
+/// Whether the path rel is below the directory dir, both
+/// from the same root.
+fn is_below(rel: &str, dir: &str) -> bool {
+    rel.strip_prefix(dir)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
 
-        if (src_is_dir && !dest_is_dir) || (!src_is_dir && dest_is_dir) {
-            fs::copy(src_sub_path, dest_sub_path).unwrap();
-        } else if !src_is_dir && !dest_is_dir {
-            // Create copied paths for read(...)
-            let src_path = src_sub_path.clone();
-            let dest_path = dest_sub_path.clone();
-            if !dest_path.exists() || (fs::read(src_path).unwrap() != fs::read(dest_path).unwrap())
-            {
-                fs::copy(src_sub_path, dest_sub_path).unwrap();
+/// Path of path from the directory dest with / separators, if
+/// it is inside, whether or not path exists. Links and relative
+/// parts of both are resolved first.
+fn rel_within(dest: &Path, path: &Path) -> Option<String> {
+    let dest = fs::canonicalize(dest).ok()?;
+    let path = env::current_dir().ok()?.join(path);
+    // Resolve the deepest ancestor that exists.
+    let mut missing = vec![];
+    let mut existing = path.as_path();
+    let mut path = loop {
+        match fs::canonicalize(existing) {
+            Ok(path) => break path,
+            Err(_) => {
+                missing.push(existing.file_name()?);
+                existing = existing.parent()?;
             }
         }
+    };
+    path.extend(missing.iter().rev());
+
+    let rel = path.strip_prefix(&dest).ok()?;
+    let names: Option<Vec<_>> = rel.iter().map(|name| name.to_str()).collect();
+    Some(names?.join("/"))
+}
+
+/// Number of entries in the tree of entry.
+fn count(entry: &Entry) -> usize {
+    1 + entry.children.iter().flatten().map(count).sum::<usize>()
+}
+
+/// Remove a file, link or whole directory.
+fn remove(path: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| Error::io(path, e))?;
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
     }
+    .map_err(|e| Error::io(path, e))
+}
+
+/// Move path to to, or to to.1, to.2 and so on if taken, so
+/// that nothing there is lost. Files are copied then removed
+/// when they cannot be renamed, as across file systems.
+fn move_to(path: &Path, to: &Path) -> Result<()> {
+    let mut free = to.to_path_buf();
+    let mut n = 0;
+    while fs::symlink_metadata(&free).is_ok() {
+        n += 1;
+        let mut name = to.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", n));
+        free = to.with_file_name(name);
+    }
+    let to = free.as_path();
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::io(parent, e))?;
+    }
+    if let Err(e) = fs::rename(path, to) {
+        if path.is_dir() || fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(Error::io(path, e));
+        }
+        fs::copy(path, to).map_err(|e| Error::io(path, e))?;
+        fs::remove_file(path).map_err(|e| Error::io(path, e))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{largest_files, sync};
-    use std::env::current_dir;
+    use super::walk::walk;
+    use super::{
+        largest_files, sync, Entry, Error, Filter, Glob, Kind, Options, Select, Symlinks,
+        SyncOptions, SyncSummary, Traversal,
+    };
+    use std::env::{current_dir, temp_dir};
     use std::fs;
-    use std::path::Path;
-    use std::str::from_utf8;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime};
+
+    /// Fresh directory under the system temp dir,
+    /// removed with everything in it on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let name = format!(
+                "rust-examples-{}-{}",
+                process::id(),
+                COUNT.fetch_add(1, Ordering::SeqCst)
+            );
+            let path = temp_dir().join(name);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+
+        /// Write a file of len bytes, creating parent directories.
+        fn file(&self, relative: &str, len: usize) {
+            let path = self.0.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![b'x'; len]).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Paths of nodes relative to root, in order.
+    fn relative(root: &Path, nodes: &[super::Node]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|node| {
+                let path = node.path.strip_prefix(root).unwrap();
+                path.to_str().unwrap().replace('\\', "/")
+            })
+            .collect()
+    }
 
     #[test]
     fn test_largest_files() {
@@ -58,66 +647,609 @@ mod tests {
             current_dir().unwrap().to_str().unwrap(),
             "/src/files/test_files"
         );
-        let res = largest_files(&test_dir, 0);
-        assert_eq!(res.unwrap().len(), 0);
-        let res = largest_files(&test_dir, 3);
-        assert_eq!(res.unwrap().len(), 3);
-        let res = largest_files(&test_dir, 5);
-        assert_eq!(res.unwrap().len(), 3);
-        let res = largest_files(&test_dir, 3);
-        if let Ok(entries) = res {
-            let entries_slice = entries.as_slice();
-            assert_eq!(entries_slice[0].file_name(), "file1.txt");
-            assert_eq!(entries_slice[1].file_name(), "file3.txt");
-            assert_eq!(entries_slice[2].file_name(), "file2.txt");
+        let res = largest_files(&test_dir, &Options::new(0));
+        assert_eq!(res.unwrap().value.len(), 0);
+        let res = largest_files(&test_dir, &Options::new(3));
+        assert_eq!(res.unwrap().value.len(), 3);
+        let res = largest_files(&test_dir, &Options::new(5));
+        assert_eq!(res.unwrap().value.len(), 3);
+        let res = largest_files(&test_dir, &Options::new(3));
+        if let Ok(outcome) = res {
+            assert!(outcome.is_complete());
+            let entries_slice = outcome.value.as_slice();
+            assert_eq!(entries_slice[0].path.file_name().unwrap(), "file1.txt");
+            assert_eq!(entries_slice[1].path.file_name().unwrap(), "file3.txt");
+            assert_eq!(entries_slice[2].path.file_name().unwrap(), "file2.txt");
         }
     }
 
     #[test]
-    fn test_sync() {
-        if let Some(current_dir) = current_dir().unwrap().to_str() {
-            // Generate test directory
-            let dest_path = format!("{}/{}", current_dir, "out");
-            let dest = Path::new(&dest_path);
-            if !dest.exists() {
-                fs::create_dir(dest).unwrap();
+    fn test_largest_files_recursive() {
+        let dir = TempDir::new();
+        dir.file("a.txt", 100);
+        dir.file("sub/b.txt", 300);
+        dir.file("sub/deep/c.txt", 50);
+        dir.file("sub/deep/d.txt", 20);
+        dir.file("other/e.txt", 200);
+
+        // Directories hold the sum of everything below them.
+        let nodes = largest_files(dir.path(), &Options::new(10)).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec![
+                "sub",
+                "sub/b.txt",
+                "other",
+                "other/e.txt",
+                "a.txt",
+                "sub/deep",
+                "sub/deep/c.txt",
+                "sub/deep/d.txt"
+            ]
+        );
+        assert_eq!(nodes[0].size, 370);
+        assert_eq!(nodes[0].kind, Kind::Dir);
+        assert_eq!(nodes[5].depth, 2);
+
+        let files = Options {
+            select: Select::Files,
+            ..Options::new(2)
+        };
+        let nodes = largest_files(dir.path(), &files).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec!["sub/b.txt", "other/e.txt"]
+        );
+
+        let dirs = Options {
+            select: Select::Dirs,
+            ..Options::new(10)
+        };
+        let nodes = largest_files(dir.path(), &dirs).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec!["sub", "other", "sub/deep"]
+        );
+    }
+
+    #[test]
+    fn test_largest_files_depth() {
+        let dir = TempDir::new();
+        dir.file("a.txt", 100);
+        dir.file("sub/b.txt", 300);
+        dir.file("sub/deep/c.txt", 50);
+
+        // Only the top level is listed, with full sizes.
+        let one = Options {
+            depth: Some(1),
+            ..Options::new(10)
+        };
+        let nodes = largest_files(dir.path(), &one).unwrap().value;
+        assert_eq!(relative(dir.path(), &nodes), vec!["sub", "a.txt"]);
+        assert_eq!(nodes[0].size, 350);
+
+        let two = Options {
+            depth: Some(2),
+            ..Options::new(10)
+        };
+        let nodes = largest_files(dir.path(), &two).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec!["sub", "sub/b.txt", "a.txt", "sub/deep"]
+        );
+    }
+
+    #[test]
+    fn test_largest_files_filter() {
+        let dir = TempDir::new();
+        dir.file("main.rs", 100);
+        dir.file("notes.txt", 400);
+        dir.file("src/lib.rs", 200);
+        dir.file("target/debug/app", 1000);
+        dir.file("vendor/dep.rs", 300);
+        dir.file("build.log", 500);
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.path().join("src/.ignore"), "/lib.rs\n").unwrap();
+
+        let filter = Filter {
+            include: vec![Glob::new("*.rs").unwrap()],
+            exclude: vec![Glob::new("target/").unwrap()],
+            ..Filter::default()
+        };
+        let options = Options {
+            filter,
+            ..Options::new(10)
+        };
+        let nodes = largest_files(dir.path(), &options).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec!["vendor", "vendor/dep.rs", "src", "src/lib.rs", "main.rs"]
+        );
+
+        let filter = Filter {
+            ignore_files: true,
+            exclude: vec![Glob::new("/vendor").unwrap()],
+            min_size: Some(150),
+            ..Filter::default()
+        };
+        let options = Options {
+            filter,
+            select: Select::Files,
+            ..Options::new(10)
+        };
+        let nodes = largest_files(dir.path(), &options).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec!["target/debug/app", "notes.txt"]
+        );
+    }
+
+    #[test]
+    fn test_sync_filter() {
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        src.file("keep.txt", 10);
+        src.file("skip.log", 10);
+
+        let options = SyncOptions {
+            filter: Filter {
+                exclude: vec![Glob::new("*.log").unwrap()],
+                ..Filter::default()
+            },
+            ..SyncOptions::default()
+        };
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(outcome.value.copied, 1);
+        assert!(dest.path().join("keep.txt").exists());
+        assert!(!dest.path().join("skip.log").exists());
+    }
+
+    #[test]
+    fn test_largest_files_errors() {
+        let dir = TempDir::new();
+        dir.file("a.txt", 10);
+        assert!(matches!(
+            largest_files(dir.path().join("a.txt"), &Options::new(1)),
+            Err(Error::NotADirectory(_))
+        ));
+        assert!(matches!(
+            largest_files(dir.path().join("missing"), &Options::new(1)),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_largest_files_warnings() {
+        let dir = TempDir::new();
+        dir.file("a.txt", 10);
+        dir.file("sub/b.txt", 20);
+        // A dangling symlink cannot be stat'ed.
+        std::os::unix::fs::symlink(dir.path().join("missing"), dir.path().join("sub/broken"))
+            .unwrap();
+
+        let options = Options {
+            traversal: Traversal {
+                symlinks: Symlinks::Follow,
+                ..Traversal::default()
+            },
+            ..Options::new(10)
+        };
+        let outcome = largest_files(dir.path(), &options).unwrap();
+        assert_eq!(
+            relative(dir.path(), &outcome.value),
+            vec!["sub", "sub/b.txt", "a.txt"]
+        );
+        assert_eq!(outcome.warnings.len(), 1);
+        assert_eq!(
+            outcome.warnings[0].path(),
+            Some(dir.path().join("sub/broken").as_path())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_largest_files_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new();
+        dir.file("a.txt", 100);
+        dir.file("sub/b.txt", 300);
+        symlink(dir.path().join("sub"), dir.path().join("link")).unwrap();
+        // Leads back to the directory holding it.
+        symlink(dir.path().join("sub"), dir.path().join("sub/loop")).unwrap();
+
+        let with = |symlinks| Options {
+            traversal: Traversal {
+                symlinks,
+                ..Traversal::default()
+            },
+            select: Select::Files,
+            ..Options::new(10)
+        };
+
+        let outcome = largest_files(dir.path(), &with(Symlinks::Skip)).unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(
+            relative(dir.path(), &outcome.value),
+            vec!["sub/b.txt", "a.txt"]
+        );
+
+        let outcome = largest_files(dir.path(), &with(Symlinks::Record)).unwrap();
+        assert!(outcome.is_complete());
+        let nodes = outcome.value;
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(
+            relative(dir.path(), &nodes[..2]),
+            vec!["sub/b.txt", "a.txt"]
+        );
+        assert!(nodes[2..].iter().all(|node| node.kind == Kind::Symlink));
+
+        // The files behind link are counted once, at their first path.
+        let outcome = largest_files(dir.path(), &with(Symlinks::Follow)).unwrap();
+        assert_eq!(
+            relative(dir.path(), &outcome.value),
+            vec!["link/b.txt", "a.txt"]
+        );
+        let loops: Vec<_> = outcome
+            .warnings
+            .iter()
+            .filter_map(|warning| match warning {
+                Error::SymlinkLoop(path) => Some(path.strip_prefix(dir.path()).unwrap()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(loops, vec![Path::new("link/loop"), Path::new("sub/loop")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_largest_files_hardlinks() {
+        let dir = TempDir::new();
+        dir.file("a/big", 500);
+        dir.file("c.txt", 100);
+        fs::create_dir(dir.path().join("b")).unwrap();
+        fs::hard_link(dir.path().join("a/big"), dir.path().join("b/big")).unwrap();
+
+        let nodes = largest_files(dir.path(), &Options::new(10)).unwrap().value;
+        assert_eq!(
+            relative(dir.path(), &nodes),
+            vec!["a", "a/big", "c.txt", "b"]
+        );
+        assert_eq!(nodes[3].size, 0);
+
+        let options = Options {
+            depth: Some(1),
+            dedup_hardlinks: false,
+            ..Options::new(10)
+        };
+        let nodes = largest_files(dir.path(), &options).unwrap().value;
+        assert_eq!(relative(dir.path(), &nodes), vec!["a", "b", "c.txt"]);
+        assert_eq!(nodes[1].size, 500);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        src.file("a.txt", 10);
+        symlink("a.txt", src.path().join("link")).unwrap();
+
+        let with = |symlinks| SyncOptions {
+            traversal: Traversal {
+                symlinks,
+                ..Traversal::default()
+            },
+            ..SyncOptions::default()
+        };
+
+        let outcome = sync(src.path(), dest.path(), &with(Symlinks::Skip)).unwrap();
+        assert_eq!(outcome.value.copied, 1);
+        assert!(fs::symlink_metadata(dest.path().join("link")).is_err());
+
+        let outcome = sync(src.path(), dest.path(), &with(Symlinks::Record)).unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(outcome.value.copied, 1);
+        assert_eq!(
+            fs::read_link(dest.path().join("link")).unwrap(),
+            Path::new("a.txt")
+        );
+        let outcome = sync(src.path(), dest.path(), &with(Symlinks::Record)).unwrap();
+        assert_eq!(outcome.value.copied, 0);
+
+        fs::remove_file(dest.path().join("link")).unwrap();
+        let outcome = sync(src.path(), dest.path(), &with(Symlinks::Follow)).unwrap();
+        assert_eq!(outcome.value.copied, 1);
+        let metadata = fs::symlink_metadata(dest.path().join("link")).unwrap();
+        assert!(metadata.file_type().is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_dest_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        let outside = TempDir::new();
+        src.file("a.txt", 10);
+        src.file("b.txt", 20);
+        outside.file("target.txt", 5);
+        symlink(outside.path().join("target.txt"), dest.path().join("a.txt")).unwrap();
+        symlink(outside.path(), dest.path().join("b.txt")).unwrap();
+
+        let outcome = sync(src.path(), dest.path(), &SyncOptions::default()).unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(outcome.value.copied, 2);
+        for (name, len) in &[("a.txt", 10), ("b.txt", 20)] {
+            let metadata = fs::symlink_metadata(dest.path().join(name)).unwrap();
+            assert!(metadata.file_type().is_file());
+            assert_eq!(metadata.len(), *len);
+        }
+        assert_eq!(
+            fs::read(outside.path().join("target.txt")).unwrap().len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_walk_threads() {
+        let dir = TempDir::new();
+        for i in 0..40 {
+            dir.file(&format!("d{}/e{}/f{}.txt", i % 5, i % 3, i), i * 7 % 50);
+        }
+        #[cfg(unix)]
+        {
+            // A second path to a file, and a link that cannot be followed.
+            fs::hard_link(dir.path().join("d0/e0/f0.txt"), dir.path().join("d4/twin")).unwrap();
+            std::os::unix::fs::symlink("missing", dir.path().join("d2/broken")).unwrap();
+        }
+
+        fn flatten(entries: &[Entry], rel: &str, out: &mut Vec<String>) {
+            for entry in entries {
+                let rel = super::filter::join(rel, entry.name.to_str().unwrap());
+                out.push(rel.clone());
+                flatten(entry.children.as_deref().unwrap_or_default(), &rel, out);
             }
+        }
+
+        let with = |threads| Traversal {
+            symlinks: Symlinks::Follow,
+            threads,
+            ..Traversal::default()
+        };
+        let outcome = walk(dir.path(), &Filter::default(), &with(1), None).unwrap();
+        let mut expected = vec![];
+        flatten(&outcome.value, "", &mut expected);
+        assert_eq!(expected.len(), 5 + 15 + 40 + cfg!(unix) as usize);
+        assert_eq!(expected[..3], ["d0", "d0/e0", "d0/e0/f0.txt"]);
+
+        for &threads in &[2, 8, 0] {
+            let outcome = walk(dir.path(), &Filter::default(), &with(threads), None).unwrap();
+            let mut rels = vec![];
+            flatten(&outcome.value, "", &mut rels);
+            assert_eq!(rels, expected);
+            assert_eq!(outcome.warnings.len(), cfg!(unix) as usize);
+        }
+
+        let ranked = |threads| {
+            let options = Options {
+                traversal: with(threads),
+                ..Options::new(100)
+            };
+            largest_files(dir.path(), &options).unwrap().value
+        };
+        let nodes = ranked(1);
+        for &threads in &[2, 8] {
+            assert_eq!(ranked(threads), nodes);
+        }
 
-            // First level file
-            if !dest.join("folder1").exists() {
-                fs::create_dir(dest.join("folder1")).unwrap();
+        // Directories at depth 2 are listed but not read.
+        let outcome = walk(dir.path(), &Filter::default(), &with(4), Some(2)).unwrap();
+        let mut rels = vec![];
+        flatten(&outcome.value, "", &mut rels);
+        assert_eq!(rels.len(), 5 + 15 + cfg!(unix) as usize);
+    }
+
+    #[test]
+    fn test_sync_delete() {
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        let backup = TempDir::new();
+        src.file("keep.txt", 10);
+        src.file("sub/new.txt", 20);
+        dest.file("keep.txt", 5);
+        dest.file("old.txt", 1);
+        dest.file("gone/a.txt", 1);
+        dest.file("gone/b.txt", 1);
+        dest.file("local.cfg", 1);
+        dest.file("sub/local.cfg", 1);
+
+        let mut options = SyncOptions {
+            delete: true,
+            max_deletions: Some(3),
+            protect: vec![Glob::new("*.cfg").unwrap()],
+            ..SyncOptions::default()
+        };
+        // old.txt, gone and the two files in it.
+        assert!(matches!(
+            sync(src.path(), dest.path(), &options),
+            Err(Error::TooManyDeletions { count: 4, max: 3 })
+        ));
+        assert!(dest.path().join("gone/a.txt").exists());
+
+        options.max_deletions = Some(4);
+        options.backup_dir = Some(backup.path().to_path_buf());
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(
+            outcome.value,
+            SyncSummary {
+                copied: 2,
+                deleted: 4
             }
-            fs::write(dest.join("folder1/hello.txt"), "HELLO").unwrap();
+        );
 
-            // Nested file (second level)
-            if !dest.join("folder1/nested").exists() {
-                fs::create_dir(dest.join("folder1/nested")).unwrap();
+        let mut left = vec![];
+        for entry in walk(dest.path(), &Filter::default(), &Traversal::default(), None)
+            .unwrap()
+            .value
+        {
+            let name = entry.name.to_str().unwrap();
+            left.push(name.to_string());
+            for child in entry.children.unwrap_or_default() {
+                left.push(format!("{}/{}", name, child.name.to_str().unwrap()));
             }
-            fs::write(dest.join("folder1/nested/world.txt"), "WORLD").unwrap();
+        }
+        assert_eq!(
+            left,
+            vec![
+                "keep.txt",
+                "local.cfg",
+                "sub",
+                "sub/local.cfg",
+                "sub/new.txt"
+            ]
+        );
 
-            // First sync
-            sync(&dest_path, &dest_path);
+        // Deleted and overwritten files are kept in the backup.
+        let read = |rel: &str| fs::read(backup.path().join(rel)).unwrap().len();
+        assert_eq!(read("keep.txt"), 5);
+        assert_eq!(read("old.txt"), 1);
+        assert_eq!(read("gone/b.txt"), 1);
 
-            assert_eq!(
-                from_utf8(
-                    fs::read(format!("{}/{}", dest_path, "folder1/hello.txt"))
-                        .unwrap()
-                        .as_slice()
-                )
-                .unwrap(),
-                "HELLO"
-            );
-            assert_eq!(
-                from_utf8(
-                    fs::read(format!("{}/{}", dest_path, "folder1/nested/world.txt"))
-                        .unwrap()
-                        .as_slice()
-                )
-                .unwrap(),
-                "WORLD"
-            );
-        } else {
-            panic!("Failed to read current directory.");
-        }
+        // A backup dir inside dest is left alone, however it is
+        // spelled, and earlier backups in it are kept.
+        options.backup_dir = Some(dest.path().join("sub/../backup"));
+        dest.file("stale.txt", 1);
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(outcome.value.deleted, 1);
+        dest.file("stale.txt", 2);
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(outcome.value.deleted, 1);
+        let read = |rel: &str| fs::read(dest.path().join(rel)).unwrap().len();
+        assert_eq!(read("backup/stale.txt"), 1);
+        assert_eq!(read("backup/stale.txt.1"), 2);
+    }
+
+    #[test]
+    fn test_sync_backup_dirs() {
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        let backup = TempDir::new();
+        src.file("keep.txt", 1);
+        dest.file("old.txt", 1);
+
+        // A backup dir in src is refused before anything moves.
+        let mut options = SyncOptions {
+            delete: true,
+            backup_dir: Some(src.path().join("bk")),
+            ..SyncOptions::default()
+        };
+        assert!(matches!(
+            sync(src.path(), dest.path(), &options),
+            Err(Error::BackupInSource(_))
+        ));
+        assert!(dest.path().join("old.txt").exists());
+        assert!(!src.path().join("bk").exists());
+
+        // Earlier backup dirs in dest outlive later syncs,
+        // with another backup dir or none.
+        options.backup_dir = Some(dest.path().join(".bak"));
+        sync(src.path(), dest.path(), &options).unwrap();
+        assert!(dest.path().join(".bak/old.txt").exists());
+
+        dest.file("stale.txt", 1);
+        options.backup_dir = Some(backup.path().to_path_buf());
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(outcome.value.deleted, 1);
+        assert!(backup.path().join("stale.txt").exists());
+
+        dest.file("stale.txt", 1);
+        options.backup_dir = None;
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(outcome.value.deleted, 1);
+        assert!(dest.path().join(".bak/old.txt").exists());
+    }
+
+    #[test]
+    fn test_sync_delete_filter() {
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        src.file("small.txt", 1);
+        src.file("big.txt", 10);
+        dest.file("small.txt", 1);
+        dest.file("extra.txt", 10);
+
+        // Files too small to copy are still in src.
+        let mut options = SyncOptions {
+            delete: true,
+            ..SyncOptions::default()
+        };
+        options.filter.min_size = Some(5);
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(
+            outcome.value,
+            SyncSummary {
+                copied: 1,
+                deleted: 1
+            }
+        );
+        assert!(dest.path().join("small.txt").exists());
+        assert!(!dest.path().join("extra.txt").exists());
+
+        // Copies are newer than src, but not extraneous.
+        options.filter.min_size = None;
+        options.filter.modified_after = Some(SystemTime::now() + Duration::from_secs(3600));
+        dest.file("extra.txt", 10);
+        let outcome = sync(src.path(), dest.path(), &options).unwrap();
+        assert_eq!(
+            outcome.value,
+            SyncSummary {
+                copied: 0,
+                deleted: 1
+            }
+        );
+        assert!(dest.path().join("small.txt").exists());
+        assert!(dest.path().join("big.txt").exists());
+    }
+
+    #[test]
+    fn test_sync() {
+        let src = TempDir::new();
+        let dest = TempDir::new();
+        src.file("top.txt", 5);
+        fs::create_dir_all(src.path().join("folder1/nested")).unwrap();
+        fs::write(src.path().join("folder1/hello.txt"), "HELLO").unwrap();
+        fs::write(src.path().join("folder1/nested/world.txt"), "WORLD").unwrap();
+        fs::create_dir(src.path().join("empty")).unwrap();
+        // A file where the source has a directory is replaced.
+        dest.file("folder1", 3);
+
+        let outcome = sync(src.path(), dest.path(), &SyncOptions::default()).unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(outcome.value.copied, 3);
+        let read = |rel: &str| fs::read_to_string(dest.path().join(rel)).unwrap();
+        assert_eq!(read("folder1/hello.txt"), "HELLO");
+        assert_eq!(read("folder1/nested/world.txt"), "WORLD");
+        assert!(dest.path().join("empty").is_dir());
+
+        // Nothing changed, nothing is copied.
+        let outcome = sync(src.path(), dest.path(), &SyncOptions::default()).unwrap();
+        assert_eq!(outcome.value.copied, 0);
+
+        fs::write(src.path().join("folder1/nested/world.txt"), "world").unwrap();
+        let outcome = sync(src.path(), dest.path(), &SyncOptions::default()).unwrap();
+        assert_eq!(outcome.value.copied, 1);
+        assert_eq!(read("folder1/nested/world.txt"), "world");
+
+        // A new destination is created.
+        let nested = dest.path().join("a/b");
+        let outcome = sync(src.path(), &nested, &SyncOptions::default()).unwrap();
+        assert_eq!(outcome.value.copied, 3);
+        assert!(nested.join("folder1/nested/world.txt").exists());
     }
 }
@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use super::Node;
+use crate::helpers::output::{csv_field, json_array, json_string};
+
+pub use crate::helpers::output::Format;
+
+const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Size in the largest binary unit that keeps it at least one,
+/// e.g. 512 B, 1.5 KiB, 20.0 MiB.
+pub fn human_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Render nodes, largest first. The table shows human readable
+/// sizes unless bytes is set. CSV and JSON always hold bytes.
+pub fn render(nodes: &[Node], format: Format, bytes: bool) -> String {
+    let mut out = String::new();
+
+    match format {
+        Format::Table => {
+            let sizes: Vec<String> = nodes
+                .iter()
+                .map(|node| {
+                    if bytes {
+                        node.size.to_string()
+                    } else {
+                        human_size(node.size)
+                    }
+                })
+                .collect();
+            let width = sizes.iter().map(String::len).max().unwrap_or(0);
+            for (node, size) in nodes.iter().zip(sizes.iter()) {
+                writeln!(
+                    out,
+                    "{:>width$}  {:<4}  {}",
+                    size,
+                    node.kind.name(),
+                    node.path.display(),
+                    width = width
+                )
+                .unwrap();
+            }
+        }
+        Format::Csv => {
+            writeln!(out, "path,size,kind,depth").unwrap();
+            for node in nodes {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    csv_field(&node.path.to_string_lossy()),
+                    node.size,
+                    node.kind.name(),
+                    node.depth
+                )
+                .unwrap();
+            }
+        }
+        Format::Json => {
+            let rows: Vec<_> = nodes
+                .iter()
+                .map(|node| {
+                    vec![
+                        ("path", json_string(&node.path.to_string_lossy())),
+                        ("size", node.size.to_string()),
+                        ("kind", json_string(node.kind.name())),
+                        ("depth", node.depth.to_string()),
+                    ]
+                })
+                .collect();
+            out.push_str(&json_array(&rows));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::Kind;
+    use std::path::PathBuf;
+
+    fn node(path: &str, size: u64, kind: Kind) -> Node {
+        Node {
+            path: PathBuf::from(path),
+            size,
+            kind,
+            depth: 1,
+        }
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1024), "1.0 KiB");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(20 * 1024 * 1024), "20.0 MiB");
+        assert_eq!(human_size(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn test_render() {
+        let nodes = vec![
+            node("big", 5 << 20, Kind::Dir),
+            node("a, \"b\".txt", 10, Kind::File),
+            node("c", 3, Kind::Symlink),
+        ];
+
+        let table = render(&nodes, Format::Table, false);
+        assert_eq!(
+            table,
+            "5.0 MiB  dir   big\n   10 B  file  a, \"b\".txt\n    3 B  link  c\n"
+        );
+        let table = render(&nodes, Format::Table, true);
+        assert!(table.starts_with("5242880  dir   big\n     10  file"));
+
+        let csv = render(&nodes, Format::Csv, false);
+        assert_eq!(
+            csv,
+            "path,size,kind,depth\nbig,5242880,dir,1\n\"a, \"\"b\"\".txt\",10,file,1\nc,3,link,1\n"
+        );
+
+        let json = render(&nodes, Format::Json, false);
+        assert!(json.contains(
+            "{\"path\": \"a, \\\"b\\\".txt\", \"size\": 10, \"kind\": \"file\", \"depth\": 1}"
+        ));
+        assert_eq!(render(&[], Format::Json, false), "[]\n");
+    }
+}
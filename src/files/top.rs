@@ -0,0 +1,70 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+const MAX_PREALLOCATED: usize = 1024;
+
+/// Keeps the n greatest items pushed so far in a
+/// min-heap, so each push costs O(log n).
+#[derive(Debug)]
+pub struct TopK<T: Ord> {
+    n: usize,
+    heap: BinaryHeap<Reverse<T>>,
+}
+
+impl<T: Ord> TopK<T> {
+    pub fn new(n: usize) -> Self {
+        TopK {
+            n,
+            // n may be far larger than the number of items.
+            heap: BinaryHeap::with_capacity(n.min(MAX_PREALLOCATED)),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.heap.len() < self.n {
+            self.heap.push(Reverse(item));
+        } else if let Some(mut smallest) = self.heap.peek_mut() {
+            // Replacing the top sifts it down once.
+            if item > smallest.0 {
+                *smallest = Reverse(item);
+            }
+        }
+    }
+
+    /// The kept items, greatest first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        // Ascending order of Reverse is descending order of T.
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(item)| item)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopK;
+
+    #[quickcheck]
+    fn test_top_k(items: Vec<i32>, n: u8) -> bool {
+        let n = n as usize % 20;
+        let mut top = TopK::new(n);
+        for &item in items.iter() {
+            top.push(item);
+        }
+
+        let mut expected = items;
+        expected.sort_by(|a, b| b.cmp(a));
+        expected.truncate(n);
+        top.into_sorted_vec() == expected
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let mut top = TopK::new(usize::MAX);
+        top.push(1);
+        top.push(3);
+        assert_eq!(top.into_sorted_vec(), vec![3, 1]);
+    }
+}
@@ -0,0 +1,416 @@
+use std::ffi::OsString;
+use std::fs::{self, Metadata, ReadDir};
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use super::filter::{self, Filter, IgnoreFile};
+use super::{Error, Outcome, Result};
+
+/// What a walk does with symbolic links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Treat links as their targets. Directory
+    /// links that lead back to an ancestor are skipped.
+    Follow,
+    /// Leave links out.
+    Skip,
+    /// Keep links as links, without reading their targets.
+    #[default]
+    Record,
+}
+
+impl FromStr for Symlinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "follow" => Ok(Symlinks::Follow),
+            "skip" => Ok(Symlinks::Skip),
+            "record" => Ok(Symlinks::Record),
+            _ => Err(format!("unknown symlink policy: {}", s)),
+        }
+    }
+}
+
+/// How a walk treats links and file system boundaries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Traversal {
+    pub symlinks: Symlinks,
+    /// Leave out entries on another device than the root.
+    pub one_file_system: bool,
+    /// Threads reading directories, 0 for one per CPU.
+    /// Results are the same for any number.
+    pub threads: usize,
+}
+
+/// Device and inode number, which tell files apart.
+pub type FileId = (u64, u64);
+
+impl Traversal {
+    /// Stat path, or None if the policy leaves it out. The
+    /// metadata is that of the link itself unless links are
+    /// followed.
+    fn stat(&self, path: &Path, root_device: Option<u64>) -> io::Result<Option<Metadata>> {
+        let metadata = fs::symlink_metadata(path)?;
+        let metadata = match (metadata.file_type().is_symlink(), self.symlinks) {
+            (true, Symlinks::Skip) => return Ok(None),
+            (true, Symlinks::Follow) => fs::metadata(path)?,
+            _ => metadata,
+        };
+        if self.one_file_system && root_device.is_some() && device(&metadata) != root_device {
+            return Ok(None);
+        }
+        Ok(Some(metadata))
+    }
+
+    fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    /// A link recorded rather than followed.
+    Symlink,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::File => "file",
+            Kind::Dir => "dir",
+            Kind::Symlink => "link",
+        }
+    }
+}
+
+/// File, directory or link kept by a walk. A walk holds the
+/// whole tree, so entries keep only what scans use; paths are
+/// rebuilt from names while descending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: OsString,
+    pub kind: Kind,
+    /// Length in bytes, of the link itself for recorded links.
+    pub len: u64,
+    /// None where the platform has no inode numbers.
+    pub id: Option<FileId>,
+    pub hard_links: u64,
+    /// Entries of a directory sorted by name, or None for
+    /// files, links and directories below the depth limit.
+    pub children: Option<Vec<Entry>>,
+}
+
+impl Entry {
+    fn new(name: OsString, metadata: &Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_symlink() {
+            Kind::Symlink
+        } else {
+            Kind::File
+        };
+        Entry {
+            name,
+            kind,
+            len: metadata.len(),
+            id: file_id(metadata),
+            hard_links: hard_links(metadata),
+            children: None,
+        }
+    }
+}
+
+/// Walk the tree under root, reading directories on the
+/// threads of the traversal, and return the entries kept by
+/// filter. Children of entries at max_depth are not read,
+/// where the root's children are at depth 1.
+///
+/// Entries come sorted by name at every level, and warnings
+/// by path, whatever the number of threads.
+pub fn walk(
+    root: &Path,
+    filter: &Filter,
+    traversal: &Traversal,
+    max_depth: Option<usize>,
+) -> Result<Outcome<Vec<Entry>>> {
+    let metadata = fs::metadata(root).map_err(|e| Error::io(root, e))?;
+    if !metadata.is_dir() {
+        return Err(Error::NotADirectory(root.to_path_buf()));
+    }
+    let entries = fs::read_dir(root).map_err(|e| Error::io(root, e))?;
+
+    let walker = Walker {
+        filter,
+        traversal,
+        max_depth,
+        root_device: device(&metadata),
+        state: Mutex::new(State {
+            pending: vec![(
+                0,
+                Job {
+                    path: root.to_path_buf(),
+                    rel: String::new(),
+                    entries: Some(entries),
+                    depth: 1,
+                    ancestors: file_id(&metadata).into_iter().collect(),
+                    ignores: Arc::new(vec![]),
+                },
+            )],
+            active: 0,
+            listings: vec![None],
+            warnings: vec![],
+        }),
+        wake: Condvar::new(),
+    };
+    thread::scope(|scope| {
+        for _ in 0..traversal.thread_count() {
+            scope.spawn(|| walker.work());
+        }
+    });
+
+    let mut state = walker.state.into_inner().unwrap();
+    let mut warnings = state.warnings;
+    warnings.sort_by(|a, b| a.path().cmp(&b.path()));
+    Ok(Outcome {
+        value: assemble(&mut state.listings, 0),
+        warnings,
+    })
+}
+
+/// A directory waiting to be read.
+struct Job {
+    path: PathBuf,
+    rel: String,
+    /// The directory, if already opened.
+    entries: Option<ReadDir>,
+    /// Depth of its entries.
+    depth: usize,
+    /// Directories above it, to catch symlink loops.
+    ancestors: Vec<FileId>,
+    /// Ignore files of the directories above it.
+    ignores: Arc<Vec<IgnoreFile>>,
+}
+
+/// Entries of a directory, with the listing index
+/// of each subdirectory that is read.
+type Listing = Vec<(Entry, Option<usize>)>;
+
+struct State {
+    /// Jobs and the listing index they fill.
+    pending: Vec<(usize, Job)>,
+    /// Number of jobs being run.
+    active: usize,
+    listings: Vec<Option<Listing>>,
+    warnings: Vec<Error>,
+}
+
+struct Walker<'a> {
+    filter: &'a Filter,
+    traversal: &'a Traversal,
+    max_depth: Option<usize>,
+    root_device: Option<u64>,
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Walker<'_> {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Run jobs until none are pending or running.
+    fn work(&self) {
+        let mut state = self.lock();
+        loop {
+            if let Some((index, job)) = state.pending.pop() {
+                state.active += 1;
+                drop(state);
+                let running = Running(self);
+                let (found, warnings) = self.read(job);
+
+                state = self.lock();
+                mem::forget(running);
+                state.active -= 1;
+                state.warnings.extend(warnings);
+                let mut listing = Vec::with_capacity(found.len());
+                for (entry, sub) in found {
+                    let child = sub.map(|sub| {
+                        let child = state.listings.len();
+                        state.listings.push(None);
+                        state.pending.push((child, sub));
+                        child
+                    });
+                    listing.push((entry, child));
+                }
+                state.listings[index] = Some(listing);
+                self.wake.notify_all();
+            } else if state.active == 0 {
+                return;
+            } else {
+                state = self.wake.wait(state).unwrap();
+            }
+        }
+    }
+
+    /// Read the directory of job, returning the entries kept,
+    /// with a job for each subdirectory to read.
+    fn read(&self, mut job: Job) -> (Vec<(Entry, Option<Job>)>, Vec<Error>) {
+        let mut warnings = vec![];
+        let mut ignores = Arc::clone(&job.ignores);
+        match self.filter.load_ignores(&job.path, &job.rel) {
+            Ok(Some(ignore)) => {
+                let mut extended = Vec::clone(&ignores);
+                extended.push(ignore);
+                ignores = Arc::new(extended);
+            }
+            Ok(None) => {}
+            Err(e) => warnings.push(e),
+        }
+
+        let opened = job.entries.take();
+        let entries = match opened.map_or_else(|| fs::read_dir(&job.path), Ok) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warnings.push(Error::io(&job.path, e));
+                return (vec![], warnings);
+            }
+        };
+        let mut names = vec![];
+        for entry in entries {
+            match entry {
+                Ok(entry) => names.push(entry.file_name()),
+                Err(e) => warnings.push(Error::io(&job.path, e)),
+            }
+        }
+        names.sort();
+
+        let mut found = vec![];
+        for name in names {
+            let path = job.path.join(&name);
+            let rel = filter::join(&job.rel, &name.to_string_lossy());
+            let metadata = match self.traversal.stat(&path, self.root_device) {
+                Ok(Some(metadata)) => metadata,
+                Ok(None) => continue,
+                Err(e) => {
+                    warnings.push(Error::io(&path, e));
+                    continue;
+                }
+            };
+
+            let mut sub = None;
+            if metadata.is_dir() {
+                if !self.filter.enters(&path, &rel, &ignores) {
+                    continue;
+                }
+                let id = file_id(&metadata);
+                if id.is_some_and(|id| job.ancestors.contains(&id)) {
+                    warnings.push(Error::SymlinkLoop(path));
+                    continue;
+                }
+                if self.max_depth.is_none_or(|max| job.depth < max) {
+                    let mut ancestors = job.ancestors.clone();
+                    ancestors.extend(id);
+                    sub = Some(Job {
+                        path,
+                        rel,
+                        entries: None,
+                        depth: job.depth + 1,
+                        ancestors,
+                        ignores: Arc::clone(&ignores),
+                    });
+                }
+            } else if !self.filter.keeps(&rel, &metadata, &ignores) {
+                continue;
+            }
+            found.push((Entry::new(name, &metadata), sub));
+        }
+        (found, warnings)
+    }
+}
+
+/// Marks a job as no longer running if reading it panics, so
+/// that the other workers stop waiting for it and the panic
+/// reaches the caller of `walk`.
+struct Running<'a, 'b>(&'a Walker<'b>);
+
+impl Drop for Running<'_, '_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.active -= 1;
+        self.0.wake.notify_all();
+    }
+}
+
+/// Build the tree below the listing at index.
+fn assemble(listings: &mut [Option<Listing>], index: usize) -> Vec<Entry> {
+    let listing = listings[index].take().unwrap_or_default();
+    listing
+        .into_iter()
+        .map(|(mut entry, child)| {
+            if let Some(child) = child {
+                entry.children = Some(assemble(listings, child));
+            }
+            entry
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+pub(crate) fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn device(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// Number of hard links to a file.
+#[cfg(unix)]
+pub(crate) fn hard_links(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn hard_links(_metadata: &Metadata) -> u64 {
+    1
+}
+
+/// Create a symbolic link at link pointing to target.
+#[cfg(unix)]
+pub(crate) fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "symbolic links are not supported",
+    ))
+}
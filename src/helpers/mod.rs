@@ -1,3 +1,5 @@
+pub mod output;
+
 use std::fmt::Display;
 use std::vec::IntoIter;
 
@@ -0,0 +1,99 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How a subcommand prints its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+/// Escape s for use inside a JSON string.
+pub fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Quote a CSV field if it holds a separator, quote or newline.
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// A JSON array with one object per line, given as
+/// (key, value) pairs whose values are already JSON.
+pub fn json_array(rows: &[Vec<(&str, String)>]) -> String {
+    if rows.is_empty() {
+        return "[]\n".to_string();
+    }
+
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|fields| {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(key, value)| format!("\"{}\": {}", key, value))
+                .collect();
+            format!("  {{{}}}", fields.join(", "))
+        })
+        .collect();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+/// A JSON string holding s.
+pub fn json_string(s: &str) -> String {
+    format!("\"{}\"", json_escape(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        assert_eq!(json_string("a \"b\"\n"), "\"a \\\"b\\\"\\n\"");
+        assert_eq!(json_array(&[]), "[]\n");
+        let rows = vec![
+            vec![("a", "1".to_string()), ("b", json_string("x"))],
+            vec![("a", "2".to_string()), ("b", json_string("y"))],
+        ];
+        assert_eq!(
+            json_array(&rows),
+            "[\n  {\"a\": 1, \"b\": \"x\"},\n  {\"a\": 2, \"b\": \"y\"}\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,12 @@
+#[cfg(test)]
+extern crate quickcheck;
+#[cfg(test)]
+#[macro_use(quickcheck)]
+extern crate quickcheck_macros;
+
+pub mod bignum;
+pub mod exponentiation;
+pub mod files;
+pub mod helpers;
+pub mod sort;
+pub mod sudoku;
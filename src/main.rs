@@ -1,20 +1,19 @@
 #[macro_use]
 extern crate clap;
 
-#[cfg(test)]
-extern crate quickcheck;
-#[cfg(test)]
-#[macro_use(quickcheck)]
-extern crate quickcheck_macros;
-
-mod exponentiation;
-mod files;
-mod helpers;
-mod sort;
-mod sudoku;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime};
+
+use clap::ArgMatches;
 
 use crossterm::Result;
 use dirs::home_dir;
+use rust_examples::bignum::BigUint;
+use rust_examples::exponentiation::cli;
+use rust_examples::files::{self, filter, report};
+use rust_examples::sort::bench;
+use rust_examples::sudoku;
 
 fn main() -> Result<()> {
     let matches = clap_app!(myapp =>
@@ -28,8 +27,67 @@ fn main() -> Result<()> {
         (@subcommand sort =>
             (about: "Run the sorting algorithms.")
         )
+        (@subcommand bench =>
+            (about: "Benchmark the sorting algorithms over input distributions.")
+            (@arg sizes: -s --sizes +takes_value +use_delimiter default_value("100,1000,10000") "Comma separated input sizes")
+            (@arg format: -f --format +takes_value possible_value[table csv json] default_value("table") "Output format")
+        )
+        (@subcommand fib =>
+            (about: "Print the exact nth fibonacci number.")
+            (@arg n: "Index of the fibonacci number")
+            (@arg mod: -m --mod +takes_value "Print the result modulo m")
+            (@arg range: -r --range +takes_value conflicts_with[n] "Print every index in a..b or a..=b")
+            (@arg algorithm: -a --algorithm +takes_value possible_value[doubling matrix linear] default_value("doubling") "Algorithm to use")
+            (@arg time: -t --time "Print how long the computation took")
+        )
+        (@subcommand pow =>
+            (about: "Print the exact value of base^exp.")
+            (@arg base: +required "Base, decimal or 0x hex")
+            (@arg exp: +required "Exponent")
+            (@arg mod: -m --mod +takes_value "Print the result modulo m")
+            (@arg algorithm: -a --algorithm +takes_value possible_value[binary monoid naive] default_value("binary") "Algorithm to use")
+            (@arg time: -t --time "Print how long the computation took")
+        )
         (@subcommand largest_files =>
             (about: "Get the n largest files from dir.")
+            (@arg path: "Directory to scan, the home directory if omitted")
+            (@arg n: -n +takes_value default_value("5") "Number of entries to show")
+            (@arg format: -f --format +takes_value possible_value[table csv json] "Output format, a table if omitted")
+            (@arg json: --json conflicts_with[format csv] "Print JSON, same as --format json")
+            (@arg csv: --csv conflicts_with[format json] "Print CSV, same as --format csv")
+            (@arg bytes: --bytes "Print exact sizes in bytes")
+            (@arg include: --include +takes_value +multiple number_of_values(1) "Only count files matching this glob")
+            (@arg exclude: --exclude +takes_value +multiple number_of_values(1) "Skip files and directories matching this glob")
+            (@arg gitignore: --gitignore "Honour .gitignore and .ignore files")
+            (@arg min_size: --("min-size") +takes_value "Skip files smaller than this, e.g. 10K")
+            (@arg max_size: --("max-size") +takes_value "Skip files larger than this, e.g. 2G")
+            (@arg newer_than: --("newer-than") +takes_value "Only files modified within this age, e.g. 7d")
+            (@arg older_than: --("older-than") +takes_value "Only files modified before this age, e.g. 12h")
+            (@arg depth: -d --depth +takes_value "Deepest level to report, the whole tree if omitted")
+            (@arg select: -s --select +takes_value possible_value[files dirs all] default_value("all") "Kinds of nodes to report")
+            (@arg symlinks: --symlinks +takes_value possible_value[follow skip record] default_value("record") "Follow symlinks, skip them or report them as links")
+            (@arg one_file_system: -x --("one-file-system") "Stay on the device of the starting directory")
+            (@arg count_hardlinks: --("count-hardlinks") "Count every path of a hard linked file")
+            (@arg threads: -j --threads +takes_value default_value("0") "Threads reading directories, 0 for one per CPU")
+        )
+        (@subcommand sync =>
+            (about: "Mirror the tree under src into dest.")
+            (@arg src: +required "Directory to copy from")
+            (@arg dest: +required "Directory to copy into, created if missing")
+            (@arg delete: --delete "Remove entries of dest missing from src")
+            (@arg max_delete: --("max-delete") +takes_value requires[delete] "Change nothing if more entries than this would be deleted")
+            (@arg protect: --protect +takes_value +multiple number_of_values(1) requires[delete] "Never delete entries matching this glob")
+            (@arg backup_dir: --("backup-dir") +takes_value "Move deleted and overwritten entries here")
+            (@arg include: --include +takes_value +multiple number_of_values(1) "Only copy files matching this glob")
+            (@arg exclude: --exclude +takes_value +multiple number_of_values(1) "Skip files and directories matching this glob")
+            (@arg gitignore: --gitignore "Honour .gitignore and .ignore files")
+            (@arg min_size: --("min-size") +takes_value "Skip files smaller than this, e.g. 10K")
+            (@arg max_size: --("max-size") +takes_value "Skip files larger than this, e.g. 2G")
+            (@arg newer_than: --("newer-than") +takes_value "Only files modified within this age, e.g. 7d")
+            (@arg older_than: --("older-than") +takes_value "Only files modified before this age, e.g. 12h")
+            (@arg symlinks: --symlinks +takes_value possible_value[follow skip record] default_value("record") "Copy symlink targets, skip links or recreate them")
+            (@arg one_file_system: -x --("one-file-system") "Stay on the device of src")
+            (@arg threads: -j --threads +takes_value default_value("0") "Threads reading directories, 0 for one per CPU")
         )
     )
     .get_matches();
@@ -42,15 +100,188 @@ fn main() -> Result<()> {
         println!("Run some sorting algorithms.");
     }
 
-    if let Some(_matches) = matches.subcommand_matches("largest_files") {
-        if let Some(home) = home_dir() {
-            let home_path = home.to_str().unwrap_or("");
-            let entries = files::largest_files(home_path, 5)?;
-            for entry in entries {
-                println!("{:?}", entry);
+    if let Some(matches) = matches.subcommand_matches("bench") {
+        let sizes: Vec<usize> = matches.values_of_t_or_exit("sizes");
+        let format: bench::Format = matches.value_of_t_or_exit("format");
+        print!("{}", bench::render(&bench::run(&sizes), format));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("fib") {
+        let modulus: Option<u64> = matches
+            .value_of("mod")
+            .map(|_| matches.value_of_t_or_exit("mod"));
+        let algorithm: cli::FibAlgorithm = matches.value_of_t_or_exit("algorithm");
+
+        let (result, time) = if matches.is_present("range") {
+            let range: cli::IndexRange = matches.value_of_t_or_exit("range");
+            cli::timed(|| cli::fib_range(&range, modulus, algorithm))
+        } else if matches.is_present("n") {
+            let n: u128 = matches.value_of_t_or_exit("n");
+            cli::timed(|| cli::fib(n, modulus, algorithm).map(|f| format!("{}\n", f)))
+        } else {
+            (
+                Err("either n or --range is required".to_string()),
+                Default::default(),
+            )
+        };
+        print_result(result, time, matches.is_present("time"));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("pow") {
+        let base: BigUint = matches.value_of_t_or_exit("base");
+        let exp: u64 = matches.value_of_t_or_exit("exp");
+        let modulus: Option<u64> = matches
+            .value_of("mod")
+            .map(|_| matches.value_of_t_or_exit("mod"));
+        let algorithm: cli::PowAlgorithm = matches.value_of_t_or_exit("algorithm");
+
+        let (result, time) =
+            cli::timed(|| cli::pow(&base, exp, modulus, algorithm).map(|p| format!("{}\n", p)));
+        print_result(result, time, matches.is_present("time"));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("largest_files") {
+        let mut options = files::Options {
+            select: matches.value_of_t_or_exit("select"),
+            filter: filter_from(matches),
+            traversal: traversal_from(matches),
+            dedup_hardlinks: !matches.is_present("count_hardlinks"),
+            ..files::Options::new(matches.value_of_t_or_exit("n"))
+        };
+        if matches.is_present("depth") {
+            options.depth = Some(matches.value_of_t_or_exit("depth"));
+        }
+        let format = if matches.is_present("json") {
+            report::Format::Json
+        } else if matches.is_present("csv") {
+            report::Format::Csv
+        } else if matches.is_present("format") {
+            matches.value_of_t_or_exit("format")
+        } else {
+            report::Format::Table
+        };
+
+        let root = match matches.value_of("path") {
+            Some(path) => PathBuf::from(path),
+            None => home_dir().unwrap_or_else(|| {
+                eprintln!("error: no home directory");
+                process::exit(1);
+            }),
+        };
+        match files::largest_files(root, &options) {
+            Ok(outcome) => {
+                print!(
+                    "{}",
+                    report::render(&outcome.value, format, matches.is_present("bytes"))
+                );
+                for warning in outcome.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("sync") {
+        let options = files::SyncOptions {
+            filter: filter_from(matches),
+            traversal: traversal_from(matches),
+            delete: matches.is_present("delete"),
+            max_deletions: matches
+                .value_of("max_delete")
+                .map(|_| matches.value_of_t_or_exit("max_delete")),
+            protect: match matches.values_of("protect") {
+                Some(_) => matches.values_of_t_or_exit("protect"),
+                None => vec![],
+            },
+            backup_dir: matches.value_of("backup_dir").map(PathBuf::from),
+        };
+
+        let src = matches.value_of("src").unwrap();
+        let dest = matches.value_of("dest").unwrap();
+        match files::sync(src, dest, &options) {
+            Ok(outcome) => {
+                println!(
+                    "copied {}, deleted {}",
+                    outcome.value.copied, outcome.value.deleted
+                );
+                for warning in outcome.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
             }
         }
     }
 
     Ok(())
 }
+
+/// Filter built from the shared scan arguments.
+fn filter_from(matches: &ArgMatches) -> files::Filter {
+    let globs = |name| match matches.values_of(name) {
+        Some(_) => matches.values_of_t_or_exit(name),
+        None => vec![],
+    };
+    let size = |name| {
+        matches.value_of(name).map(|s| {
+            filter::parse_size(s).unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            })
+        })
+    };
+    let age = |name| {
+        matches.value_of(name).map(|s| {
+            filter::parse_age(s)
+                .and_then(|age| {
+                    SystemTime::now()
+                        .checked_sub(age)
+                        .ok_or_else(|| format!("invalid age: {}", s))
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                })
+        })
+    };
+
+    files::Filter {
+        include: globs("include"),
+        exclude: globs("exclude"),
+        ignore_files: matches.is_present("gitignore"),
+        min_size: size("min_size"),
+        max_size: size("max_size"),
+        modified_after: age("newer_than"),
+        modified_before: age("older_than"),
+        skip_marked: None,
+    }
+}
+
+/// Link and device handling from the shared scan arguments.
+fn traversal_from(matches: &ArgMatches) -> files::Traversal {
+    files::Traversal {
+        symlinks: matches.value_of_t_or_exit("symlinks"),
+        one_file_system: matches.is_present("one_file_system"),
+        threads: matches.value_of_t_or_exit("threads"),
+    }
+}
+
+/// Print a result, or the error and exit with failure.
+fn print_result(result: std::result::Result<String, String>, time: Duration, show_time: bool) {
+    match result {
+        Ok(out) => print!("{}", out),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+    if show_time {
+        eprintln!("time: {:.3} ms", time.as_secs_f64() * 1e3);
+    }
+}
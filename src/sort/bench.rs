@@ -0,0 +1,263 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use super::mergesort::MergeSort;
+use super::quicksort::QuickSort;
+use super::Sorter;
+use crate::helpers::output::{json_array, json_string};
+
+pub use crate::helpers::output::Format;
+
+/// Fixed seed so runs are comparable over time.
+const SEED: u64 = 0x5eed;
+
+/// Number of timed runs per measurement. The fastest is kept.
+const RUNS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    Random,
+    Sorted,
+    Reversed,
+    FewUnique,
+    OrganPipe,
+    Sawtooth,
+}
+
+impl Distribution {
+    pub const ALL: [Distribution; 6] = [
+        Distribution::Random,
+        Distribution::Sorted,
+        Distribution::Reversed,
+        Distribution::FewUnique,
+        Distribution::OrganPipe,
+        Distribution::Sawtooth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Distribution::Random => "random",
+            Distribution::Sorted => "sorted",
+            Distribution::Reversed => "reversed",
+            Distribution::FewUnique => "few-unique",
+            Distribution::OrganPipe => "organ-pipe",
+            Distribution::Sawtooth => "sawtooth",
+        }
+    }
+
+    /// Generate n values following this distribution.
+    pub fn generate(self, n: usize) -> Vec<i64> {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let n_i64 = n as i64;
+
+        match self {
+            Distribution::Random => (0..n).map(|_| rng.gen()).collect(),
+            Distribution::Sorted => (0..n_i64).collect(),
+            Distribution::Reversed => (0..n_i64).rev().collect(),
+            Distribution::FewUnique => (0..n).map(|_| rng.gen_range(0, 16)).collect(),
+            // Ascending to the middle, then descending.
+            Distribution::OrganPipe => (0..n_i64).map(|i| i.min(n_i64 - 1 - i)).collect(),
+            // Ascending runs of length sqrt(n).
+            Distribution::Sawtooth => {
+                let period = ((n as f64).sqrt() as i64).max(1);
+                (0..n_i64).map(|i| i % period).collect()
+            }
+        }
+    }
+}
+
+/// Value that counts how many times it is compared.
+#[derive(Debug, Clone, Copy)]
+struct Counted<'a> {
+    value: i64,
+    comparisons: &'a Cell<u64>,
+}
+
+impl PartialEq for Counted<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Counted<'_> {}
+
+impl PartialOrd for Counted<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Counted<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.comparisons.set(self.comparisons.get() + 1);
+        self.value.cmp(&other.value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub sorter: &'static str,
+    pub distribution: Distribution,
+    pub size: usize,
+    pub time: Duration,
+    pub comparisons: u64,
+}
+
+/// Time sorter over one input and count its comparisons.
+pub fn measure<S: Sorter>(
+    name: &'static str,
+    sorter: &S,
+    distribution: Distribution,
+    size: usize,
+) -> Record {
+    let input = distribution.generate(size);
+
+    let time = (0..RUNS)
+        .map(|_| {
+            let mut arr = input.clone();
+            let start = Instant::now();
+            sorter.sort(&mut arr);
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default();
+
+    let comparisons = Cell::new(0);
+    let mut counted: Vec<_> = input
+        .iter()
+        .map(|&value| Counted {
+            value,
+            comparisons: &comparisons,
+        })
+        .collect();
+    sorter.sort(&mut counted);
+
+    Record {
+        sorter: name,
+        distribution,
+        size,
+        time,
+        comparisons: comparisons.get(),
+    }
+}
+
+/// Run every sorter over every distribution and size.
+pub fn run(sizes: &[usize]) -> Vec<Record> {
+    let mut records = vec![];
+    for &size in sizes {
+        for &distribution in Distribution::ALL.iter() {
+            records.push(measure("mergesort", &MergeSort, distribution, size));
+            records.push(measure("quicksort", &QuickSort, distribution, size));
+        }
+    }
+    records
+}
+
+pub fn render(records: &[Record], format: Format) -> String {
+    let mut out = String::new();
+
+    match format {
+        Format::Table => {
+            writeln!(
+                out,
+                "{:<10} {:<12} {:>10} {:>12} {:>14}",
+                "sorter", "distribution", "size", "time (ms)", "comparisons"
+            )
+            .unwrap();
+            for r in records {
+                writeln!(
+                    out,
+                    "{:<10} {:<12} {:>10} {:>12.3} {:>14}",
+                    r.sorter,
+                    r.distribution.name(),
+                    r.size,
+                    r.time.as_secs_f64() * 1e3,
+                    r.comparisons
+                )
+                .unwrap();
+            }
+        }
+        Format::Csv => {
+            writeln!(out, "sorter,distribution,size,nanos,comparisons").unwrap();
+            for r in records {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    r.sorter,
+                    r.distribution.name(),
+                    r.size,
+                    r.time.as_nanos(),
+                    r.comparisons
+                )
+                .unwrap();
+            }
+        }
+        Format::Json => {
+            let rows: Vec<_> = records
+                .iter()
+                .map(|r| {
+                    vec![
+                        ("sorter", json_string(r.sorter)),
+                        ("distribution", json_string(r.distribution.name())),
+                        ("size", r.size.to_string()),
+                        ("nanos", r.time.as_nanos().to_string()),
+                        ("comparisons", r.comparisons.to_string()),
+                    ]
+                })
+                .collect();
+            out.push_str(&json_array(&rows));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distributions() {
+        for &distribution in Distribution::ALL.iter() {
+            assert_eq!(distribution.generate(0).len(), 0);
+            assert_eq!(distribution.generate(100).len(), 100);
+        }
+        assert_eq!(Distribution::OrganPipe.generate(6), vec![0, 1, 2, 2, 1, 0]);
+        assert_eq!(Distribution::Sawtooth.generate(6), vec![0, 1, 0, 1, 0, 1]);
+        assert_eq!(
+            Distribution::Random.generate(10),
+            Distribution::Random.generate(10)
+        );
+    }
+
+    #[test]
+    fn test_counts_comparisons() {
+        // Two elements are merged with a single comparison.
+        let record = measure("mergesort", &MergeSort, Distribution::Sorted, 2);
+        assert_eq!(record.comparisons, 1);
+        let record = measure("mergesort", &MergeSort, Distribution::Sorted, 0);
+        assert_eq!(record.comparisons, 0);
+    }
+
+    #[test]
+    fn test_render() {
+        let records = run(&[8]);
+        assert_eq!(records.len(), 2 * Distribution::ALL.len());
+
+        let csv = render(&records, Format::Csv);
+        assert_eq!(csv.lines().count(), records.len() + 1);
+        assert!(csv.starts_with("sorter,distribution,size,nanos,comparisons\n"));
+
+        let json = render(&records, Format::Json);
+        assert!(json.starts_with("[\n"));
+        assert_eq!(json.matches("\"sorter\"").count(), records.len());
+
+        let table = render(&records, Format::Table);
+        assert!(table.contains("organ-pipe"));
+    }
+}
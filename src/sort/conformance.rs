@@ -0,0 +1,240 @@
+//! Conformance checks that any `Sorter` implementation can run.
+//!
+//! ```
+//! use rust_examples::sort::{conformance, mergesort::MergeSort};
+//!
+//! conformance::check_edge_cases(&MergeSort).unwrap();
+//! assert!(conformance::check(&MergeSort, &[3, 1, 2]).is_ok());
+//! assert!(conformance::check_stability(&MergeSort, &[3, 1, 3, 2]).is_ok());
+//! ```
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::Sorter;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// `output[index] > output[index + 1]`.
+    NotSorted { index: usize },
+    /// Output does not hold the same elements as the input.
+    NotPermutation,
+    /// Equal keys at `index` and `index + 1` were reordered
+    /// by a sorter that claims to be stable.
+    NotStable { index: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::NotSorted { index } => {
+                write!(f, "output is not sorted at index {}", index)
+            }
+            Violation::NotPermutation => write!(f, "output is not a permutation of input"),
+            Violation::NotStable { index } => {
+                write!(f, "equal elements reordered at index {}", index)
+            }
+        }
+    }
+}
+
+pub type Result = std::result::Result<(), Violation>;
+
+/// Returns index of first element which is greater
+/// than its successor, if any.
+pub fn first_unsorted<T: Ord>(arr: &[T]) -> Option<usize> {
+    arr.windows(2).position(|pair| pair[0] > pair[1])
+}
+
+/// True if both slices contain the same elements
+/// with the same multiplicities.
+pub fn is_permutation<T: Ord + Clone>(xs: &[T], ys: &[T]) -> bool {
+    if xs.len() != ys.len() {
+        return false;
+    }
+
+    let mut xs = xs.to_vec();
+    let mut ys = ys.to_vec();
+    xs.sort();
+    ys.sort();
+    xs == ys
+}
+
+/// Sorts a copy of input and checks that the result is
+/// ordered and a permutation of input.
+pub fn check<S, T>(sorter: &S, input: &[T]) -> Result
+where
+    S: Sorter,
+    T: Ord + Copy,
+{
+    let mut output = input.to_vec();
+    sorter.sort(&mut output);
+
+    if let Some(index) = first_unsorted(&output) {
+        return Err(Violation::NotSorted { index });
+    }
+    if !is_permutation(input, &output) {
+        return Err(Violation::NotPermutation);
+    }
+
+    Ok(())
+}
+
+/// Element that is ordered by key only, remembering
+/// its original position in the input.
+#[derive(Debug, Clone, Copy)]
+struct Tagged {
+    key: i64,
+    position: usize,
+}
+
+impl PartialEq for Tagged {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Tagged {}
+
+impl PartialOrd for Tagged {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Tagged {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// Checks that equal keys keep their input order.
+/// Always passes for sorters that do not claim stability.
+pub fn check_stability<S: Sorter>(sorter: &S, keys: &[i64]) -> Result {
+    if !sorter.is_stable() {
+        return Ok(());
+    }
+
+    let mut tagged: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .map(|(position, &key)| Tagged { key, position })
+        .collect();
+    sorter.sort(&mut tagged);
+
+    match tagged
+        .windows(2)
+        .position(|pair| pair[0].key == pair[1].key && pair[0].position > pair[1].position)
+    {
+        Some(index) => Err(Violation::NotStable { index }),
+        None => Ok(()),
+    }
+}
+
+/// Inputs that sorters commonly get wrong.
+pub fn edge_cases() -> Vec<Vec<i64>> {
+    vec![
+        vec![],
+        vec![42],
+        vec![2, 1],
+        vec![1, 2],
+        vec![7; 64],
+        vec![i64::MAX, i64::MIN],
+        vec![i64::MIN, 0, i64::MAX, i64::MIN, i64::MAX, -1, 1],
+        vec![i64::MAX; 8],
+        vec![i64::MIN; 8],
+        (0..100).collect(),
+        (0..100).rev().collect(),
+        (0..100).map(|x| x % 3).collect(),
+    ]
+}
+
+/// Runs `check` and `check_stability` over all edge cases.
+pub fn check_edge_cases<S: Sorter>(sorter: &S) -> Result {
+    for input in edge_cases() {
+        check(sorter, &input)?;
+        check_stability(sorter, &input)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sorter that does nothing. Should fail on any unsorted input.
+    struct Identity;
+
+    impl Sorter for Identity {
+        fn sort<T>(&self, _arr: &mut [T])
+        where
+            T: Ord + Copy,
+        {
+        }
+    }
+
+    /// Sorter that sorts but loses elements.
+    struct Lossy;
+
+    impl Sorter for Lossy {
+        fn sort<T>(&self, arr: &mut [T])
+        where
+            T: Ord + Copy,
+        {
+            arr.sort_unstable();
+            if arr.len() > 1 {
+                arr[1] = arr[0];
+            }
+        }
+    }
+
+    /// Unstable sorter that wrongly claims stability.
+    struct Reversing;
+
+    impl Sorter for Reversing {
+        fn sort<T>(&self, arr: &mut [T])
+        where
+            T: Ord + Copy,
+        {
+            arr.sort_by(|a, b| b.cmp(a));
+            arr.reverse();
+        }
+
+        fn is_stable(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_detects_unsorted() {
+        assert_eq!(
+            check(&Identity, &[1, 3, 2]),
+            Err(Violation::NotSorted { index: 1 })
+        );
+        assert_eq!(check(&Identity, &[1, 2, 3]), Ok(()));
+    }
+
+    #[test]
+    fn test_detects_lost_elements() {
+        assert_eq!(check(&Lossy, &[3, 1, 2]), Err(Violation::NotPermutation));
+    }
+
+    #[test]
+    fn test_detects_unstable() {
+        assert_eq!(
+            check_stability(&Reversing, &[1, 1]),
+            Err(Violation::NotStable { index: 0 })
+        );
+        assert_eq!(
+            check_edge_cases(&Reversing),
+            Err(Violation::NotStable { index: 0 })
+        );
+    }
+
+    #[quickcheck]
+    fn test_is_permutation(xs: Vec<i64>) -> bool {
+        let mut ys = xs.clone();
+        ys.reverse();
+        is_permutation(&xs, &ys)
+    }
+}
@@ -0,0 +1,408 @@
+use std::cmp::Ordering;
+use std::collections::{LinkedList, VecDeque};
+use std::iter::FromIterator;
+use std::mem;
+
+use super::Sorter;
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+}
+
+/// Singly linked list whose nodes can be
+/// relinked while sorting.
+pub struct List<T> {
+    head: Link<T>,
+    len: usize,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List { head: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let next = self.head.take();
+        self.head = Some(Box::new(Node { value, next }));
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.take().map(|node| {
+            self.head = node.next;
+            self.len -= 1;
+            node.value
+        })
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+}
+
+impl<T: Ord> List<T> {
+    /// Bottom-up merge sort. Nodes are relinked in place,
+    /// so only O(1) extra space is used. Stable.
+    pub fn merge_sort(&mut self) {
+        let mut width = 1;
+        while width < self.len {
+            let mut rest = self.head.take();
+            let mut tail = &mut self.head;
+
+            while rest.is_some() {
+                let mut left = rest;
+                let mut right = split_off(&mut left, width);
+                rest = split_off(&mut right, width);
+
+                *tail = merge(left, right);
+                while tail.is_some() {
+                    tail = &mut tail.as_mut().unwrap().next;
+                }
+            }
+
+            width *= 2;
+        }
+    }
+}
+
+/// Detach and return everything after the first n nodes.
+fn split_off<T>(link: &mut Link<T>, n: usize) -> Link<T> {
+    let mut cursor = link;
+    for _ in 0..n {
+        if cursor.is_none() {
+            return None;
+        }
+        cursor = &mut cursor.as_mut().unwrap().next;
+    }
+    cursor.take()
+}
+
+/// Merge two sorted chains, preferring left on ties.
+fn merge<T: Ord>(mut left: Link<T>, mut right: Link<T>) -> Link<T> {
+    let mut head = None;
+    let mut tail = &mut head;
+
+    while let (Some(l), Some(r)) = (&left, &right) {
+        let source = if l.value <= r.value {
+            &mut left
+        } else {
+            &mut right
+        };
+        let mut node = source.take().unwrap();
+        *source = node.next.take();
+        tail = &mut tail.insert(node).next;
+    }
+    *tail = if left.is_some() { left } else { right };
+
+    head
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+impl<T> Drop for List<T> {
+    // Unlink iteratively, the default drop recurses once per node.
+    fn drop(&mut self) {
+        let mut link = self.head.take();
+        while let Some(mut node) = link {
+            link = node.next.take();
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = List::new();
+        let mut tail = &mut list.head;
+        for value in iter {
+            tail = &mut tail.insert(Box::new(Node { value, next: None })).next;
+            list.len += 1;
+        }
+        list
+    }
+}
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            &node.value
+        })
+    }
+}
+
+pub struct IntoIter<T>(List<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop_front()
+    }
+}
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// Bottom-up merge sort of a std `LinkedList`.
+///
+/// `LinkedList` does not expose its nodes, so runs are
+/// split with `split_off` and merged by moving values
+/// one at a time. Stable.
+pub fn sort_linked_list<T: Ord>(list: &mut LinkedList<T>) {
+    let len = list.len();
+    let mut width = 1;
+    while width < len {
+        let mut rest = mem::take(list);
+
+        while !rest.is_empty() {
+            let mut left = rest;
+            let mut right = left.split_off(width.min(left.len()));
+            rest = right.split_off(width.min(right.len()));
+
+            while let (Some(l), Some(r)) = (left.front(), right.front()) {
+                let source = if l <= r { &mut left } else { &mut right };
+                list.extend(source.pop_front());
+            }
+            list.append(&mut left);
+            list.append(&mut right);
+        }
+
+        width *= 2;
+    }
+}
+
+/// Element of a deque half, ordered by the element it
+/// refers to. Copy whatever the element type, so that any
+/// sorter can sort it.
+struct Key<'a, T> {
+    value: &'a T,
+    index: usize,
+}
+
+impl<T> Clone for Key<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Key<'_, T> {}
+
+impl<T: Ord> PartialEq for Key<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Ord> Eq for Key<'_, T> {}
+
+impl<T: Ord> PartialOrd for Key<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Key<'_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(other.value)
+    }
+}
+
+/// Keys of half, whose first element sits at offset.
+fn keys<T>(half: &[T], offset: usize) -> Vec<Key<'_, T>> {
+    half.iter()
+        .enumerate()
+        .map(|(i, value)| Key {
+            value,
+            index: offset + i,
+        })
+        .collect()
+}
+
+/// Sort both halves of the ring buffer with sorter, then
+/// merge them. Elements are never copied into a Vec, and
+/// each is swapped into place once, so T need not be Copy.
+///
+/// Uses O(n) extra space: `Sorter::sort` only takes Copy
+/// elements, so the sorter orders keys borrowing the
+/// elements, and their merged order is kept as n indices
+/// until the permutation is applied.
+pub fn sort_vec_deque<S, T>(sorter: &S, deque: &mut VecDeque<T>)
+where
+    S: Sorter,
+    T: Ord,
+{
+    let mut order = {
+        let (front, back) = deque.as_slices();
+        let mut front_keys = keys(front, 0);
+        let mut back_keys = keys(back, front.len());
+        sorter.sort(&mut front_keys);
+        sorter.sort(&mut back_keys);
+
+        // Prefer the front half on ties to keep a stable sort stable.
+        let mut order = Vec::with_capacity(deque.len());
+        let (mut i, mut j) = (0, 0);
+        while i < front_keys.len() && j < back_keys.len() {
+            if front_keys[i] <= back_keys[j] {
+                order.push(front_keys[i].index);
+                i += 1;
+            } else {
+                order.push(back_keys[j].index);
+                j += 1;
+            }
+        }
+        order.extend(front_keys[i..].iter().map(|key| key.index));
+        order.extend(back_keys[j..].iter().map(|key| key.index));
+        order
+    };
+
+    // Position k takes the element at order[k]. Walk each
+    // cycle of the permutation, marking done positions.
+    for start in 0..order.len() {
+        let mut current = start;
+        loop {
+            let source = mem::replace(&mut order[current], current);
+            if source == start || source == current {
+                break;
+            }
+            deque.swap(current, source);
+            current = source;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{LinkedList, VecDeque};
+
+    use super::super::mergesort::MergeSort;
+    use super::super::quicksort::QuickSort;
+    use super::{sort_linked_list, sort_vec_deque, List};
+
+    fn sorted(xs: &[isize]) -> Vec<isize> {
+        let mut expected = xs.to_vec();
+        expected.sort();
+        expected
+    }
+
+    #[quickcheck]
+    fn test_list_merge_sort(xs: Vec<isize>) -> bool {
+        let mut list: List<_> = xs.iter().copied().collect();
+        list.merge_sort();
+        list.len() == xs.len() && list.into_iter().collect::<Vec<_>>() == sorted(&xs)
+    }
+
+    #[quickcheck]
+    fn test_list_merge_sort_stable(keys: Vec<u8>) -> bool {
+        // Sort (key, position) pairs by key only.
+        let mut list: List<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(position, &key)| Keyed(key, position))
+            .collect();
+        list.merge_sort();
+
+        let values: Vec<_> = list.iter().collect();
+        values
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0 || (pair[0].0 == pair[1].0 && pair[0].1 < pair[1].1))
+    }
+
+    #[derive(Debug)]
+    struct Keyed(u8, usize);
+
+    impl PartialEq for Keyed {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Keyed {}
+
+    impl PartialOrd for Keyed {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Keyed {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    #[test]
+    fn test_long_list() {
+        let mut list: List<_> = (0..200_000).rev().collect();
+        list.merge_sort();
+        assert!(list.iter().copied().eq(0..200_000));
+    }
+
+    #[quickcheck]
+    fn test_sort_linked_list(xs: Vec<isize>) -> bool {
+        let mut list: LinkedList<_> = xs.iter().copied().collect();
+        sort_linked_list(&mut list);
+        list.into_iter().collect::<Vec<_>>() == sorted(&xs)
+    }
+
+    #[quickcheck]
+    fn test_sort_vec_deque(xs: Vec<isize>, ys: Vec<isize>) -> bool {
+        // Pushing to the front wraps the ring buffer,
+        // so both halves are non-empty.
+        let mut deque: VecDeque<_> = VecDeque::with_capacity(xs.len() + ys.len());
+        deque.extend(ys.iter().copied());
+        for &x in xs.iter() {
+            deque.push_front(x);
+        }
+        let mut other = deque.clone();
+
+        let mut expected: Vec<_> = deque.iter().copied().collect();
+        expected.sort();
+
+        sort_vec_deque(&MergeSort, &mut deque);
+        sort_vec_deque(&QuickSort, &mut other);
+        deque.iter().eq(expected.iter()) && other.iter().eq(expected.iter())
+    }
+
+    #[quickcheck]
+    fn test_sort_vec_deque_strings(xs: Vec<String>, ys: Vec<String>) -> bool {
+        let mut deque: VecDeque<_> = VecDeque::with_capacity(xs.len() + ys.len());
+        deque.extend(ys);
+        for x in xs {
+            deque.push_front(x);
+        }
+        let mut expected: Vec<_> = deque.iter().cloned().collect();
+        expected.sort();
+
+        sort_vec_deque(&MergeSort, &mut deque);
+        deque.iter().eq(expected.iter())
+    }
+}
@@ -1,6 +1,6 @@
 use super::Sorter;
 
-struct MergeSort;
+pub struct MergeSort;
 
 impl MergeSort {
     fn merge<T>(&self, arr: &mut [T], s1: usize, s2: usize, end: usize)
@@ -18,7 +18,8 @@ impl MergeSort {
         let mut j = s2;
         let mut k = s1;
         while i < temp.len() && j <= end {
-            if temp[i] < arr[j] {
+            // Prefer the left run on ties to keep the sort stable.
+            if temp[i] <= arr[j] {
                 arr[k] = temp[i];
                 i += 1;
             } else {
@@ -59,10 +60,15 @@ impl Sorter for MergeSort {
             MergeSort.merge_sort_helper(arr, 0, arr.len() - 1);
         }
     }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::conformance;
     use super::MergeSort;
     use super::Sorter; // Reveal trait for sort() method
 
@@ -117,4 +123,15 @@ mod tests {
 
         true
     }
+
+    #[quickcheck]
+    fn test_conformance(xs: Vec<i64>) -> bool {
+        conformance::check(&MergeSort, &xs).is_ok()
+            && conformance::check_stability(&MergeSort, &xs).is_ok()
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert_eq!(conformance::check_edge_cases(&MergeSort), Ok(()));
+    }
 }
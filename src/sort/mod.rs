@@ -1,3 +1,6 @@
+pub mod bench;
+pub mod conformance;
+pub mod linked;
 pub mod mergesort;
 pub mod quicksort;
 
@@ -5,4 +8,10 @@ pub trait Sorter {
     fn sort<T>(&self, arr: &mut [T])
     where
         T: Ord + Copy;
+
+    /// Whether elements that compare equal keep their
+    /// relative order. Checked by the conformance suite.
+    fn is_stable(&self) -> bool {
+        false
+    }
 }
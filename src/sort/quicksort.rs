@@ -68,7 +68,7 @@ impl Sorter for QuickSort {
 mod tests {
     use rand::prelude::*;
 
-    use super::super::Sorter;
+    use super::super::{conformance, Sorter};
     use super::QuickSort;
 
     pub fn random(start: usize, limit: usize, default: usize) -> usize {
@@ -127,4 +127,14 @@ mod tests {
             true
         })
     }
+
+    #[quickcheck]
+    fn test_conformance(xs: Vec<i64>) -> bool {
+        conformance::check(&QuickSort, &xs).is_ok()
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert_eq!(conformance::check_edge_cases(&QuickSort), Ok(()));
+    }
 }